@@ -0,0 +1,98 @@
+//! Times scans with each collector while this process holds many listeners.
+//!
+//! Run with `cargo bench`; `BENCH_LISTENERS` sets how many sockets to open
+//! (default 2000) and `BENCH_ROUNDS` how many scans to average (default 10).
+
+use port_checker::ports::{Collector, ScanOptions, Scanner};
+use std::env;
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::{Duration, Instant};
+
+fn env_number(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Allow this process as many open files as the hard limit permits
+fn raise_fd_limit() {
+    use nix::libc;
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit/setrlimit only read and write `limit`
+    unsafe {
+        if libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) == 0 {
+            limit.rlim_cur = limit.rlim_max;
+            libc::setrlimit(libc::RLIMIT_NOFILE, &limit);
+        }
+    }
+}
+
+fn mean(samples: &[Duration]) -> f64 {
+    samples.iter().map(Duration::as_secs_f64).sum::<f64>() * 1000.0 / samples.len() as f64
+}
+
+fn main() {
+    // `cargo test --benches` runs this with --bench absent; skip the work then
+    if !env::args().any(|a| a == "--bench") {
+        return;
+    }
+
+    raise_fd_limit();
+    let wanted = env_number("BENCH_LISTENERS", 2000);
+    let rounds = env_number("BENCH_ROUNDS", 10).max(1);
+
+    let mut listeners = Vec::with_capacity(wanted);
+    for _ in 0..wanted {
+        match TcpListener::bind((Ipv4Addr::LOCALHOST, 0)) {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => {
+                eprintln!("stopped after {} listeners: {e}", listeners.len());
+                break;
+            }
+        }
+    }
+    println!(
+        "{} listeners in this process, {rounds} rounds",
+        listeners.len()
+    );
+
+    let collectors = if cfg!(target_os = "linux") {
+        vec![Collector::Netstat2, Collector::SockDiag]
+    } else {
+        vec![Collector::Netstat2]
+    };
+    for collector in collectors {
+        let mut options = ScanOptions::default();
+        options.collector = collector;
+
+        let mut cold = Vec::new();
+        let mut found = 0;
+        for _ in 0..rounds {
+            let start = Instant::now();
+            let report = Scanner::new().scan(&options).expect("scan");
+            cold.push(start.elapsed());
+            found = report.ports.len();
+        }
+
+        let mut scanner = Scanner::new();
+        scanner.scan(&options).expect("scan");
+        let mut warm = Vec::new();
+        for _ in 0..rounds {
+            let start = Instant::now();
+            scanner.scan(&options).expect("scan");
+            warm.push(start.elapsed());
+        }
+
+        println!(
+            "{:<10} cold {:>8.2} ms  warm {:>8.2} ms  ({found} ports)",
+            collector.name(),
+            mean(&cold),
+            mean(&warm)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Times building the rows of a large table and drawing frames of it.
+//!
+//! Run with `cargo bench --bench render`; `BENCH_ROWS` sets how many
+//! listeners to show (default 50000) and `BENCH_ROUNDS` how many frames to
+//! average (default 100).
+
+// The TUI is part of the binary, so its modules are compiled in here too.
+// Much of them goes unused, as do the imports of their tests in test builds.
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/agent.rs"]
+mod agent;
+#[path = "../src/app.rs"]
+mod app;
+#[path = "../src/backend.rs"]
+mod backend;
+#[path = "../src/cli.rs"]
+mod cli;
+#[path = "../src/docker.rs"]
+mod docker;
+#[path = "../src/elevate.rs"]
+mod elevate;
+#[path = "../src/history.rs"]
+mod history;
+#[path = "../src/metrics.rs"]
+mod metrics;
+#[path = "../src/orphan.rs"]
+mod orphan;
+#[path = "../src/proctree.rs"]
+mod proctree;
+#[path = "../src/project.rs"]
+mod project;
+#[path = "../src/recognize.rs"]
+mod recognize;
+#[path = "../src/snapshot.rs"]
+mod snapshot;
+#[path = "../src/stale.rs"]
+mod stale;
+#[path = "../src/systemd.rs"]
+mod systemd;
+#[path = "../src/ui.rs"]
+mod ui;
+
+use app::App;
+use port_checker::ports::PortInfo;
+use ratatui::{backend::TestBackend, Terminal};
+use snapshot::Snapshot;
+use std::env;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Instant;
+
+fn env_number(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// `count` node servers on distinct ports and PIDs
+fn synthetic_ports(count: usize) -> Vec<PortInfo> {
+    (0..count)
+        .map(|i| {
+            let pid = 10_000 + i as u32;
+            let mut p = PortInfo::new(
+                (i % 65_535) as u16 + 1,
+                Ipv4Addr::LOCALHOST.into(),
+                pid,
+                "node",
+            );
+            p.exe_path = Some(PathBuf::from(format!("/srv/app-{i}/bin/node")));
+            p.cwd = Some(PathBuf::from(format!("/srv/app-{i}")));
+            p.cmd_args = vec!["node".to_string(), "server.js".to_string()];
+            p.uid = Some(1000);
+            p.user = Some("dev".to_string());
+            p
+        })
+        .collect()
+}
+
+fn main() {
+    // `cargo test --benches` runs this with --bench absent; skip the work then
+    if !env::args().any(|a| a == "--bench") {
+        return;
+    }
+
+    let rows = env_number("BENCH_ROWS", 50_000);
+    let rounds = env_number("BENCH_ROUNDS", 100).max(1);
+    let snapshot = Snapshot::new(synthetic_ports(rows));
+
+    let start = Instant::now();
+    let mut app = App::from_snapshot(snapshot);
+    println!("rebuild {rows} rows: {:?}", start.elapsed());
+
+    let mut terminal = Terminal::new(TestBackend::new(160, 50)).expect("terminal");
+    let start = Instant::now();
+    for i in 0..rounds {
+        app.table_state.select(Some(i * rows / rounds));
+        terminal
+            .draw(|frame| ui::render(frame, &mut app))
+            .expect("draw");
+    }
+    println!(
+        "frame: {:?} over {rounds} rounds",
+        start.elapsed() / rounds as u32
+    );
+}
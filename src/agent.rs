@@ -0,0 +1,570 @@
+use port_checker::ports::{self, PortInfo, ScanOptions, ScanReport, ScanWarning};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Environment variable holding the shared token when `--token-file` is not given
+pub const TOKEN_ENV: &str = "PORT_CHECKER_TOKEN";
+
+/// Timeout for connecting to an agent and for each request
+const IO_TIMEOUT_SECS: u64 = 10;
+
+/// Connections that send nothing for this long are dropped, so idle clients
+/// do not hold a thread forever. Clients reconnect on their next request.
+const IDLE_TIMEOUT_SECS: u64 = 60;
+
+/// Longest accepted message line, so a bad peer cannot exhaust memory
+const MAX_MESSAGE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Where an agent listens: `tcp://host:port` (or plain `host:port`) or `unix:/path`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AgentAddr {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl AgentAddr {
+    pub fn parse(s: &str) -> Self {
+        if let Some(path) = s.strip_prefix("unix:") {
+            AgentAddr::Unix(PathBuf::from(path))
+        } else {
+            AgentAddr::Tcp(s.strip_prefix("tcp://").unwrap_or(s).to_string())
+        }
+    }
+}
+
+impl fmt::Display for AgentAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentAddr::Tcp(addr) => write!(f, "tcp://{addr}"),
+            AgentAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// One request per line, as JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    /// Must be the first request on every connection
+    Hello {
+        token: String,
+    },
+    Scan,
+    Kill {
+        pid: u32,
+        force: bool,
+    },
+}
+
+/// One response line per request
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum Response {
+    Welcome {
+        hostname: String,
+    },
+    Ports {
+        ports: Vec<PortInfo>,
+        /// Absent from agents that predate scan warnings
+        #[serde(default)]
+        warnings: Vec<ScanWarning>,
+    },
+    Killed,
+    Error {
+        message: String,
+    },
+}
+
+trait Stream: Read + Write + Send {}
+impl<T: Read + Write + Send> Stream for T {}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Serve scans made with `options` and kill requests on `addr` until the
+/// process is stopped
+pub fn serve(
+    addr: &AgentAddr,
+    token: String,
+    options: ScanOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = bind(addr)?;
+    eprintln!("Agent listening on {addr}");
+    accept_loop(listener, token, options);
+    Ok(())
+}
+
+fn bind(addr: &AgentAddr) -> io::Result<Listener> {
+    match addr {
+        AgentAddr::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr)?)),
+        AgentAddr::Unix(path) => {
+            // Replace a socket left behind by a previous agent, but nothing else
+            match fs::symlink_metadata(path) {
+                Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path)?,
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} exists and is not a socket", path.display()),
+                    ))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            // Bind in a directory only we can enter and move the socket into
+            // place once it is private, so nobody can connect in between
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let dir = path.with_file_name(format!(".{name}.{}", std::process::id()));
+            fs::DirBuilder::new().mode(0o700).create(&dir)?;
+            let bound = dir.join("agent.sock");
+            let listener = UnixListener::bind(&bound).and_then(|listener| {
+                fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+                fs::rename(&bound, path)?;
+                Ok(listener)
+            });
+            let _ = fs::remove_file(&bound);
+            fs::remove_dir(&dir)?;
+            Ok(Listener::Unix(listener?))
+        }
+    }
+}
+
+fn accept_loop(listener: Listener, token: String, options: ScanOptions) {
+    loop {
+        let timeout = Some(Duration::from_secs(IDLE_TIMEOUT_SECS));
+        let stream: io::Result<Box<dyn Stream>> = match &listener {
+            Listener::Tcp(l) => l.accept().and_then(|(s, _)| {
+                s.set_read_timeout(timeout)?;
+                Ok(Box::new(s) as Box<dyn Stream>)
+            }),
+            Listener::Unix(l) => l.accept().and_then(|(s, _)| {
+                s.set_read_timeout(timeout)?;
+                Ok(Box::new(s) as Box<dyn Stream>)
+            }),
+        };
+        match stream {
+            Ok(stream) => {
+                let token = token.clone();
+                let options = options.clone();
+                thread::spawn(move || {
+                    match handle_client(stream, &token, &options) {
+                        // An idle client, dropped after IDLE_TIMEOUT_SECS
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(e) => eprintln!("Agent connection failed: {e}"),
+                        Ok(()) => {}
+                    }
+                });
+            }
+            Err(e) => eprintln!("Accept failed: {e}"),
+        }
+    }
+}
+
+fn handle_client(stream: Box<dyn Stream>, token: &str, options: &ScanOptions) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    match read_message(&mut reader)? {
+        Some(Request::Hello { token: given }) if tokens_match(&given, token) => {
+            let hostname = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+            write_message(reader.get_mut(), &Response::Welcome { hostname })?;
+        }
+        _ => {
+            let message = "authentication failed".to_string();
+            return write_message(reader.get_mut(), &Response::Error { message });
+        }
+    }
+
+    while let Some(request) = read_message::<Request>(&mut reader)? {
+        let response = match request {
+            Request::Hello { .. } => Response::Error {
+                message: "already authenticated".to_string(),
+            },
+            Request::Scan => match ports::scan(options) {
+                Ok(ScanReport {
+                    ports, warnings, ..
+                }) => Response::Ports { ports, warnings },
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            },
+            Request::Kill { pid, force } => kill_listener(pid, force, options),
+        };
+        write_message(reader.get_mut(), &response)?;
+    }
+    Ok(())
+}
+
+/// Signal `pid`, refusing processes that do not own a listening socket so a
+/// client cannot use the agent to kill arbitrary processes. Sockets are
+/// looked up with the agent's `options`, like the scans clients are shown.
+fn kill_listener(pid: u32, force: bool, options: &ScanOptions) -> Response {
+    let owns_listener = match ports::scan(options) {
+        Ok(report) => {
+            report.ports.iter().any(|p| p.pid == pid)
+                || report.unix_sockets.iter().any(|u| u.pid == pid)
+        }
+        Err(e) => {
+            return Response::Error {
+                message: e.to_string(),
+            }
+        }
+    };
+    if !owns_listener {
+        return Response::Error {
+            message: format!("PID {pid} does not own a listening socket"),
+        };
+    }
+
+    match port_checker::signal::send_signal(pid, force) {
+        Ok(()) => Response::Killed,
+        Err(e) => Response::Error {
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Compare tokens without returning early on the first differing byte
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    if reader.take(MAX_MESSAGE_BYTES).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(message).map_err(io::Error::other)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+/// Connection to a remote agent, reconnecting on the next request after a failure
+pub struct AgentClient {
+    pub addr: AgentAddr,
+    token: String,
+    /// Hostname reported by the agent, once connected
+    pub hostname: Option<String>,
+    conn: Option<BufReader<Box<dyn Stream>>>,
+}
+
+impl AgentClient {
+    pub fn new(addr: AgentAddr, token: String) -> Self {
+        Self {
+            addr,
+            token,
+            hostname: None,
+            conn: None,
+        }
+    }
+
+    /// Name for the host column: the agent's hostname, or its address until connected
+    pub fn name(&self) -> String {
+        self.hostname
+            .clone()
+            .unwrap_or_else(|| self.addr.to_string())
+    }
+
+    pub fn scan(&mut self) -> Result<ScanReport, String> {
+        match self.request(&Request::Scan)? {
+            Response::Ports { ports, warnings } => Ok(ScanReport {
+                ports,
+                warnings,
+                ..Default::default()
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn kill(&mut self, pid: u32, force: bool) -> Result<(), String> {
+        match self.request(&Request::Kill { pid, force })? {
+            Response::Killed => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response, String> {
+        // A kept-alive connection may have been dropped by the agent since the
+        // last request, so retry once on a fresh connection
+        let reused = self.conn.is_some();
+        match self.try_request(request) {
+            Err(_) if reused => self.try_request(request),
+            result => result,
+        }
+        .map_err(|e| format!("{}: {e}", self.addr))
+    }
+
+    fn try_request(&mut self, request: &Request) -> io::Result<Response> {
+        if self.conn.is_none() {
+            self.connect()?;
+        }
+        let conn = self.conn.as_mut().expect("connected above");
+
+        let result = write_message(conn.get_mut(), request).and_then(|()| {
+            read_message(conn)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "agent closed"))
+        });
+        if result.is_err() {
+            self.conn = None;
+        }
+        result
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        let timeout = Some(Duration::from_secs(IO_TIMEOUT_SECS));
+        let stream: Box<dyn Stream> = match &self.addr {
+            AgentAddr::Tcp(addr) => {
+                let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "address did not resolve")
+                })?;
+                let stream =
+                    TcpStream::connect_timeout(&addr, Duration::from_secs(IO_TIMEOUT_SECS))?;
+                stream.set_read_timeout(timeout)?;
+                Box::new(stream)
+            }
+            AgentAddr::Unix(path) => {
+                let stream = UnixStream::connect(path)?;
+                stream.set_read_timeout(timeout)?;
+                Box::new(stream)
+            }
+        };
+
+        let mut conn = BufReader::new(stream);
+        let hello = Request::Hello {
+            token: self.token.clone(),
+        };
+        write_message(conn.get_mut(), &hello)?;
+        match read_message(&mut conn)? {
+            Some(Response::Welcome { hostname }) => {
+                self.hostname = Some(hostname);
+                self.conn = Some(conn);
+                Ok(())
+            }
+            Some(Response::Error { message }) => {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, message))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected handshake response",
+            )),
+        }
+    }
+}
+
+fn unexpected(response: Response) -> String {
+    match response {
+        Response::Error { message } => message,
+        other => format!("unexpected response: {other:?}"),
+    }
+}
+
+#[cfg(test)]
+pub use loopback::{spawn_agent, spawn_child, wait_for_exit};
+
+#[cfg(test)]
+mod loopback {
+    use super::*;
+    use std::process::{Child, Command, ExitStatus, Stdio};
+    use std::time::Instant;
+
+    /// Set in children to what they do: `listen` or `idle`
+    pub const CHILD_ENV: &str = "PORT_CHECKER_AGENT_CHILD";
+
+    /// Printed by a child once it is ready to be killed
+    pub const READY: &str = "@child ready";
+
+    /// An agent accepting connections on a loopback port, served by a
+    /// thread of this process
+    pub fn spawn_agent(token: &str) -> AgentAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind loopback");
+        let addr = AgentAddr::Tcp(listener.local_addr().unwrap().to_string());
+        let token = token.to_string();
+        thread::spawn(move || accept_loop(Listener::Tcp(listener), token, ScanOptions::default()));
+        addr
+    }
+
+    /// This test binary re-run as a process that listens on a loopback
+    /// port if `listen` is set, then waits to be killed. Returns once the
+    /// child is ready.
+    pub fn spawn_child(listen: bool) -> Child {
+        spawn_child_in(if listen { "listen" } else { "idle" })
+    }
+
+    /// Like [`spawn_child`], with the child's mode: `listen`, `unix` to
+    /// listen on an abstract Unix socket only, or `idle`
+    pub fn spawn_child_in(mode: &str) -> Child {
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "agent::tests::agent_child",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(CHILD_ENV, mode)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("spawn child");
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        // libtest's "test agent_child ... " may precede the marker
+        for line in stdout.lines() {
+            if line.unwrap().contains(READY) {
+                return child;
+            }
+        }
+        let _ = child.wait();
+        panic!("child exited before it was ready");
+    }
+
+    /// How `child` exited, or `None` if it is still running after `timeout`
+    pub fn wait_for_exit(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait().unwrap() {
+                return Some(status);
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::loopback::{spawn_child_in, CHILD_ENV, READY};
+    use super::*;
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+    use std::os::unix::process::ExitStatusExt;
+
+    /// Child process body for the kill tests. A no-op when run as a normal
+    /// test.
+    #[test]
+    fn agent_child() {
+        let Ok(mode) = std::env::var(CHILD_ENV) else {
+            return;
+        };
+        let _listener =
+            (mode == "listen").then(|| TcpListener::bind("127.0.0.1:0").expect("bind loopback"));
+        let _unix = (mode == "unix").then(|| {
+            let name = format!("port-checker-test-{}", std::process::id());
+            let addr = SocketAddr::from_abstract_name(name).unwrap();
+            UnixListener::bind_addr(&addr).expect("bind abstract socket")
+        });
+        println!("{READY}");
+        loop {
+            thread::sleep(Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn test_agent_scan_over_loopback() {
+        let addr = spawn_agent("secret");
+
+        let mut client = AgentClient::new(addr.clone(), "secret".to_string());
+        client.scan().expect("scan through agent");
+        assert!(client.hostname.is_some());
+
+        let mut intruder = AgentClient::new(addr, "guess".to_string());
+        let err = intruder.scan().unwrap_err();
+        assert!(err.contains("authentication failed"), "{err}");
+    }
+
+    #[test]
+    fn test_agent_kill_over_loopback() {
+        let mut child = spawn_child(true);
+        let addr = spawn_agent("secret");
+
+        let mut client = AgentClient::new(addr, "secret".to_string());
+        let killed = client.kill(child.id(), false);
+        let status = wait_for_exit(&mut child, Duration::from_secs(5));
+        if status.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        killed.expect("kill through agent");
+        assert_eq!(status.and_then(|s| s.signal()), Some(nix::libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_kill_listener_refuses_other_processes() {
+        let mut child = spawn_child(false);
+        let response = kill_listener(child.id(), true, &ScanOptions::default());
+        let running = child.try_wait().unwrap().is_none();
+        let _ = child.kill();
+        let _ = child.wait();
+
+        match response {
+            Response::Error { message } => {
+                assert!(
+                    message.contains("does not own a listening socket"),
+                    "{message}"
+                )
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+        assert!(running);
+    }
+
+    #[test]
+    fn test_kill_listener_uses_agent_options() {
+        // Only a Unix listener, which the agent sees when it scans them
+        let mut child = spawn_child_in("unix");
+        let refused = kill_listener(child.id(), false, &ScanOptions::default());
+        let mut options = ScanOptions::default();
+        options.unix = true;
+        let killed = kill_listener(child.id(), false, &options);
+        let status = wait_for_exit(&mut child, Duration::from_secs(5));
+        if status.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        assert!(matches!(refused, Response::Error { .. }), "{refused:?}");
+        assert!(matches!(killed, Response::Killed), "{killed:?}");
+        assert_eq!(status.and_then(|s| s.signal()), Some(nix::libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_bind_unix_socket() {
+        let dir = std::env::temp_dir().join(format!("port-checker-bind-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("agent.sock");
+        let addr = AgentAddr::Unix(path.clone());
+
+        // A regular file in the way is left alone
+        fs::write(&path, "notes").unwrap();
+        assert!(bind(&addr).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+        fs::remove_file(&path).unwrap();
+
+        // A stale socket is replaced, and the new one is private
+        drop(bind(&addr).unwrap());
+        let listener = bind(&addr).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        UnixStream::connect(&path).expect("connect to the moved socket");
+        drop(listener);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,24 @@
-use crate::ports::{get_listening_ports, PortInfo};
+use crate::agent::AgentClient;
+use crate::backend::{ScanBackend, SystemBackend};
+use crate::docker::{self, Container, ContainerAction};
+use crate::elevate::Elevation;
+use crate::history::History;
+use crate::proctree::{ProcEntry, ProcessTree};
+use crate::project::Project;
+use crate::recognize;
+use crate::snapshot::{self, DiffEntry, Snapshot};
+use crate::systemd::{Unit, UnitAction};
+use port_checker::ports::{
+    self, Namespace, PortInfo, ScanError, ScanOptions, ScanReport, ScanWarning, UnixSocketInfo,
+};
 use ratatui::widgets::TableState;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Instant;
 
 /// How long status messages are shown (seconds)
@@ -8,67 +27,1527 @@ const STATUS_DISPLAY_DURATION_SECS: u64 = 2;
 /// Horizontal scroll step size
 const SCROLL_STEP: u16 = 10;
 
-#[derive(Clone, Copy, PartialEq, Eq, Default)]
+/// Spinner frames shown while a scan runs in the background
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long each spinner frame is shown (milliseconds)
+const SPINNER_FRAME_MS: u128 = 80;
+
+/// Timeline scrub step sizes (seconds) with their labels
+pub const TIMELINE_STEPS: [(u64, &str); 4] =
+    [(60, "1m"), (600, "10m"), (3600, "1h"), (86_400, "1d")];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum PopupButton {
     #[default]
     Cancel,
     Terminate,
     ForceKill,
+    /// Only offered for rows that belong to a container
+    StopContainer,
+    RestartContainer,
+    /// Only offered for rows of a systemd unit outside any container
+    StopUnit,
+    RestartUnit,
+    UnitStatus,
+    /// Only offered for other rows running a stale binary
+    RestartProcess,
+}
+
+/// Where the rows in the table come from
+pub enum Source {
+    /// Live scan of this machine
+    Live,
+    /// Saved snapshot loaded from disk (read-only)
+    Snapshot { label: String },
+    /// Diff of a baseline snapshot against another snapshot, or against the
+    /// live state when `against` is `None`
+    Diff {
+        baseline: Snapshot,
+        against: Option<Snapshot>,
+    },
+    /// Recorded listener history, showing the listeners open at `at`
+    Timeline {
+        history: History,
+        at: u64,
+        /// Index into `TIMELINE_STEPS`
+        step: usize,
+    },
+    /// Ports reported by remote agents
+    Remote {
+        /// Shared with the worker thread while it scans them
+        agents: Vec<Arc<Mutex<AgentClient>>>,
+        /// Name of each agent as of the last scan, for the host column
+        names: Vec<String>,
+    },
+}
+
+/// Which kind of listener the table shows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Tcp,
+    /// Listening Unix domain sockets, in the live view only
+    Unix,
+}
+
+/// Which network namespaces the TCP table shows
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum NamespaceFilter {
+    #[default]
+    All,
+    /// Only the scanner's own namespace
+    Host,
+    Only(Namespace),
+}
+
+impl NamespaceFilter {
+    fn matches(&self, port: &PortInfo) -> bool {
+        match self {
+            NamespaceFilter::All => true,
+            NamespaceFilter::Host => port.namespace.is_none(),
+            NamespaceFilter::Only(namespace) => port.namespace.as_ref() == Some(namespace),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            NamespaceFilter::All => "all",
+            NamespaceFilter::Host => "host",
+            NamespaceFilter::Only(namespace) => &namespace.name,
+        }
+    }
+}
+
+/// Whose process a row belongs to, which colors the row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    /// The user running port-checker
+    Mine,
+    Root,
+    /// Some other user
+    Other,
+    Unknown,
+}
+
+impl Owner {
+    fn of(uid: Option<u32>, me: u32) -> Self {
+        match uid {
+            Some(uid) if uid == me => Owner::Mine,
+            Some(0) => Owner::Root,
+            Some(_) => Owner::Other,
+            None => Owner::Unknown,
+        }
+    }
+}
+
+/// Display strings for one table row, built when the rows change rather
+/// than on every frame
+pub struct RowText {
+    /// Every column but the last, in header order
+    pub cells: Vec<String>,
+    /// Last column, scrolled horizontally: the executable path of a TCP
+    /// row or the socket path of a Unix row
+    pub scrolled: String,
+    pub owner: Owner,
+    /// Indexes into `ports` of every port of the group when this is a
+    /// process or project row of the grouped view
+    pub group: Option<Vec<usize>>,
+}
+
+/// How the TCP table groups its rows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grouping {
+    /// One row per port
+    #[default]
+    None,
+    Process,
+    Project,
+}
+
+/// The group a port belongs to in the grouped view
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum GroupKey {
+    /// The agent a remote process runs under, and its PID
+    Process(Option<usize>, u32),
+    /// Root of a project, or `None` for ports outside any project
+    Project(Option<PathBuf>),
+}
+
+/// A finished scan, with the containers and units behind its listeners
+struct Scanned {
+    report: ScanReport,
+    containers: Vec<Container>,
+    /// Container each port of `report` runs in, by its cgroup
+    container_ids: Vec<Option<String>>,
+    /// Unit of each port of `report`
+    units: Vec<Option<Unit>>,
+    /// Project of each port of `report`
+    projects: Vec<Option<Project>>,
+    /// Whether each port of `report` belongs to an orphaned dev server
+    orphans: Vec<bool>,
+    /// Whether each port of `report` belongs to a process running a stale
+    /// binary
+    stale: Vec<bool>,
+}
+
+type ScanResult = Result<Scanned, ScanError>;
+
+/// Ports reported by the remote agents that answered
+struct RemoteScanned {
+    /// Index of the reporting agent and port, sorted by port
+    rows: Vec<(usize, PortInfo)>,
+    warnings: Vec<ScanWarning>,
+    /// Why the other agents did not answer
+    errors: Vec<String>,
+    /// Name of each agent, which is its hostname once it answered
+    names: Vec<String>,
+}
+
+/// What a worker thread sends back
+enum ScanOutcome {
+    Local(ScanResult),
+    Remote(RemoteScanned),
+}
+
+/// A scan running on a worker thread
+struct ScanJob {
+    rx: Receiver<ScanOutcome>,
+    started: Instant,
+    /// Whether to report the result in the status bar
+    announce: bool,
+    /// Set to have the worker stop at its next stage
+    cancel: Arc<AtomicBool>,
+}
+
+/// A restart running on a worker thread, which waits for the old process
+/// to exit before starting the new one
+struct RestartJob {
+    /// Process name and PID of the listener being restarted
+    name: String,
+    pid: u32,
+    rx: Receiver<io::Result<u32>>,
+    started: Instant,
 }
 
 pub struct App {
     pub ports: Vec<PortInfo>,
+    /// Listening Unix sockets from the last live scan
+    pub unix_sockets: Vec<UnixSocketInfo>,
+    /// Running Docker containers from the last live scan
+    pub containers: Vec<Container>,
+    /// ID of the container each row in `ports` runs in by its cgroup, from
+    /// the last live scan
+    pub container_ids: Vec<Option<String>>,
+    /// systemd unit of each row in `ports` from the last live scan
+    pub units: Vec<Option<Unit>>,
+    /// Whether some ports belong to a unit, which adds a unit column
+    pub unit_column: bool,
+    /// Project each row in `ports` runs from, from the last live scan
+    pub projects: Vec<Option<Project>>,
+    /// Whether some ports run from a project, which adds project and
+    /// branch columns
+    pub project_column: bool,
+    /// Whether each row in `ports` belongs to a dev server whose terminal
+    /// is gone, from the last live scan
+    pub orphans: Vec<bool>,
+    /// Whether each row in `ports` runs a deleted or rebuilt binary, from
+    /// the last live scan
+    pub stale: Vec<bool>,
+    /// Whether some rows are flagged, which adds a flags column
+    pub flags_column: bool,
+    pub view: View,
+    pub namespace_filter: NamespaceFilter,
+    /// Whether some ports are in other network namespaces, which adds a
+    /// namespace column
+    pub namespace_column: bool,
+    /// Whether live scans look into other network namespaces, which
+    /// `--namespaces` turns on
+    scans_namespaces: bool,
+    /// Display strings for the rows of the current view: `ports` or
+    /// `unix_sockets`
+    pub rows: Vec<RowText>,
+    /// Index into `ports`, or `unix_sockets` in the Unix view, of each row
+    pub shown: Vec<usize>,
+    /// Show only the listeners of processes run by `uid`
+    pub mine_only: bool,
+    /// Show only the listeners of orphaned dev servers
+    pub orphans_only: bool,
+    /// Group the TCP view by process or project
+    pub grouping: Grouping,
+    /// Groups whose ports are listed below them
+    expanded: HashSet<GroupKey>,
+    /// Real UID of this process, the "me" of `mine_only`
+    pub uid: u32,
+    pub source: Source,
+    /// Scans and signals this machine for the live view and live diffs
+    backend: Arc<dyn ScanBackend>,
+    /// Background scan whose result has not arrived yet
+    scan: Option<ScanJob>,
+    /// Cancelled scan whose worker has not stopped yet
+    cancelled: Option<ScanJob>,
+    /// Scan to start once the cancelled one stopped, and whether to
+    /// announce it
+    queued_scan: Option<bool>,
+    /// Restart of a stale process, rescanned once it is done
+    restart: Option<RestartJob>,
+    /// Diff entries matching `ports` row for row when `source` is `Diff`
+    pub diff: Vec<DiffEntry>,
+    /// Index of the owning agent for each row in `ports` when `source` is `Remote`
+    pub hosts: Vec<usize>,
     pub table_state: TableState,
     pub error: Option<String>,
+    /// What the last scan could not see, such as other users' sockets
+    pub warnings: Vec<ScanWarning>,
     pub scroll_offset: u16,
     pub status_message: Option<String>,
     pub status_time: Option<Instant>,
     pub show_terminate_popup: bool,
     pub popup_selection: PopupButton,
+    /// Parents and children of the popup's process, in the live view
+    pub tree: Option<ProcessTree>,
+    /// Highlighted entry of `tree`, as an index into its entries
+    pub tree_cursor: usize,
+    /// Action waiting for the user to confirm running it as root
+    pub elevation_prompt: Option<Elevation>,
+    /// Confirmed action for the event loop to run with the TUI suspended
+    pub pending_elevation: Option<Elevation>,
+    /// Whether the rows come from a scan run as root
+    pub elevated: bool,
+    /// Unit whose `systemctl status` the event loop should show
+    pub pending_unit_status: Option<Unit>,
+    /// Orphaned dev servers awaiting confirmation to terminate them, as
+    /// (PID, description)
+    pub cleanup_prompt: Option<Vec<(u32, String)>>,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self::new()
+impl App {
+    /// Live view of this machine, scanned with `options`
+    pub fn new(options: ScanOptions) -> Self {
+        let scans_namespaces = options.namespaces;
+        let mut app = Self::with_backend(Box::new(SystemBackend::new(options)));
+        app.scans_namespaces = scans_namespaces;
+        app
     }
-}
 
-impl App {
-    pub fn new() -> Self {
-        let (ports, error) = match get_listening_ports() {
-            Ok(p) => (p, None),
-            Err(e) => (vec![], Some(e.to_string())),
-        };
+    /// Live view of whatever `backend` reports. The first scan runs in the
+    /// background like every later one.
+    pub fn with_backend(backend: Box<dyn ScanBackend>) -> Self {
+        let mut app = Self::with_source(vec![], None, Source::Live);
+        app.backend = Arc::from(backend);
+        app.start_scan(false);
+        app
+    }
 
-        let mut table_state = TableState::default();
-        if !ports.is_empty() {
-            table_state.select(Some(0));
-        }
+    /// Browse a saved snapshot without scanning or killing anything
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        let label = snapshot.label();
+        Self::with_source(snapshot.ports, None, Source::Snapshot { label })
+    }
+
+    /// Show the diff of `baseline` against `against`, or against the live
+    /// state scanned with `options` when `against` is `None`
+    pub fn from_diff(baseline: Snapshot, against: Option<Snapshot>, options: ScanOptions) -> Self {
+        let mut app = Self::with_source(vec![], None, Source::Diff { baseline, against });
+        app.backend = Arc::new(SystemBackend::new(options));
+        app.load_diff(None);
+        app
+    }
 
-        Self {
+    /// Browse recorded history, starting at the most recent event
+    pub fn from_history(history: History) -> Self {
+        let at = history.span().map(|(_, last)| last).unwrap_or(0);
+        let ports = history.state_at(at);
+        let error = history
+            .events
+            .is_empty()
+            .then(|| "No history recorded yet - run `port-checker daemon`".to_string());
+        Self::with_source(
             ports,
-            table_state,
             error,
+            Source::Timeline {
+                history,
+                at,
+                step: 0,
+            },
+        )
+    }
+
+    /// Browse the ports of one or more remote agents
+    pub fn from_agents(agents: Vec<AgentClient>) -> Self {
+        let names = agents.iter().map(AgentClient::name).collect();
+        let agents = agents
+            .into_iter()
+            .map(|a| Arc::new(Mutex::new(a)))
+            .collect();
+        let mut app = Self::with_source(vec![], None, Source::Remote { agents, names });
+        app.start_scan(false);
+        app
+    }
+
+    fn with_source(ports: Vec<PortInfo>, error: Option<String>, source: Source) -> Self {
+        let mut app = Self {
+            ports,
+            unix_sockets: vec![],
+            containers: vec![],
+            container_ids: vec![],
+            units: vec![],
+            unit_column: false,
+            projects: vec![],
+            project_column: false,
+            orphans: vec![],
+            stale: vec![],
+            flags_column: false,
+            view: View::default(),
+            namespace_filter: NamespaceFilter::default(),
+            namespace_column: false,
+            scans_namespaces: false,
+            rows: vec![],
+            shown: vec![],
+            mine_only: false,
+            orphans_only: false,
+            grouping: Grouping::None,
+            expanded: HashSet::new(),
+            uid: nix::unistd::getuid().as_raw(),
+            source,
+            backend: Arc::new(SystemBackend::default()),
+            scan: None,
+            cancelled: None,
+            queued_scan: None,
+            restart: None,
+            diff: vec![],
+            hosts: vec![],
+            table_state: TableState::default(),
+            error,
+            warnings: vec![],
             scroll_offset: 0,
             status_message: None,
             status_time: None,
             show_terminate_popup: false,
             popup_selection: PopupButton::default(),
+            tree: None,
+            tree_cursor: 0,
+            elevation_prompt: None,
+            pending_elevation: None,
+            elevated: false,
+            pending_unit_status: None,
+            cleanup_prompt: None,
+        };
+        app.rows_changed();
+        app
+    }
+
+    /// Whether the rows describe processes that can be acted on
+    pub fn is_read_only(&self) -> bool {
+        !matches!(self.source, Source::Live | Source::Remote { .. })
+    }
+
+    pub fn title(&self) -> String {
+        match &self.source {
+            Source::Live if self.view == View::Unix => format!(
+                " Listening Unix Sockets ({}){}{} ",
+                self.rows.len(),
+                if self.mine_only { " [mine]" } else { "" },
+                if self.elevated { " [root scan]" } else { "" }
+            ),
+            Source::Live => {
+                let count = if self.grouping == Grouping::None {
+                    self.rows.len()
+                } else {
+                    self.rows
+                        .iter()
+                        .filter_map(|r| r.group.as_ref())
+                        .map(Vec::len)
+                        .sum()
+                };
+                let mut title = format!(" Listening TCP Ports ({count}) ");
+                match self.grouping {
+                    Grouping::None => {}
+                    Grouping::Process => title.push_str("[by process] "),
+                    Grouping::Project => title.push_str("[by project] "),
+                }
+                if self.namespace_filter != NamespaceFilter::All {
+                    title.push_str(&format!("[netns {}] ", self.namespace_filter.label()));
+                }
+                if self.mine_only {
+                    title.push_str("[mine] ");
+                }
+                if self.orphans_only {
+                    title.push_str("[orphans] ");
+                }
+                if self.elevated {
+                    title.push_str("[root scan] ");
+                }
+                title
+            }
+            Source::Snapshot { label } => {
+                format!(" Snapshot: {label} ({}) [read-only] ", self.ports.len())
+            }
+            Source::Diff { baseline, against } => {
+                let against = against
+                    .as_ref()
+                    .map(|s| s.label())
+                    .unwrap_or_else(|| "live".to_string());
+                format!(" Diff: {} -> {against} [read-only] ", baseline.label())
+            }
+            Source::Remote { agents, .. } => format!(
+                " Listening TCP Ports on {} agent{} ({}) ",
+                agents.len(),
+                if agents.len() == 1 { "" } else { "s" },
+                self.ports.len()
+            ),
+            Source::Timeline { at, step, .. } => format!(
+                " History @ {} ({}) step {} [read-only] ",
+                snapshot::format_timestamp(*at),
+                self.ports.len(),
+                TIMELINE_STEPS[*step].1
+            ),
         }
     }
 
     pub fn refresh(&mut self) {
-        self.set_status("Refreshing...");
-        match get_listening_ports() {
-            Ok(p) => {
-                self.ports = p;
-                self.error = None;
-                self.adjust_selection();
-                self.set_status(&format!("Refreshed - {} ports", self.ports.len()));
+        match self.source {
+            Source::Live => {}
+            Source::Snapshot { .. } => {
+                self.set_status("Snapshot is read-only");
+                return;
+            }
+            Source::Timeline { .. } => {
+                self.set_status("Use [ ] to scrub the timeline");
+                return;
+            }
+            Source::Diff {
+                against: Some(_), ..
+            } => {
+                self.load_diff(None);
+                self.set_status(&format!("Refreshed - {} differences", self.diff_count()));
+                return;
+            }
+            Source::Diff { .. } | Source::Remote { .. } => {}
+        }
+
+        self.start_scan(true);
+    }
+
+    /// Scan this machine, or the remote agents, on a worker thread;
+    /// `poll_scan` picks up the result. Does nothing while a scan is already
+    /// running.
+    fn start_scan(&mut self, announce: bool) {
+        if let Some(job) = &mut self.scan {
+            job.announce |= announce;
+            return;
+        }
+        // The cancelled worker may still hold the scanner or an agent, and
+        // a new scan would only wait behind it
+        if self.cancelled.is_some() {
+            self.queued_scan = Some(self.queued_scan.unwrap_or(false) | announce);
+            self.set_status("Waiting for the cancelled scan to stop");
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancelled = Arc::clone(&cancel);
+        // Nothing is sent when the scan is cancelled
+        if let Source::Remote { agents, .. } = &self.source {
+            let agents = agents.clone();
+            thread::spawn(move || {
+                if let Some(scanned) = scan_agents(&agents, &cancelled) {
+                    let _ = tx.send(ScanOutcome::Remote(scanned));
+                }
+            });
+        } else {
+            let backend = Arc::clone(&self.backend);
+            // Unix sockets are only looked up while they are shown
+            let unix = self.view == View::Unix;
+            thread::spawn(move || {
+                if let Some(result) = scan_local(backend.as_ref(), unix, &cancelled) {
+                    let _ = tx.send(ScanOutcome::Local(result));
+                }
+            });
+        }
+        self.scan = Some(ScanJob {
+            rx,
+            started: Instant::now(),
+            announce,
+            cancel,
+        });
+    }
+
+    /// Apply the background scan's result if it has arrived
+    pub fn poll_scan(&mut self) {
+        if let Some(job) = &self.restart {
+            let result = match job.rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => {
+                    Some(Err(io::Error::other("restart thread panicked")))
+                }
+            };
+            if let Some(result) = result {
+                self.finish_restart(result);
+            }
+        }
+
+        if let Some(job) = &self.cancelled {
+            if let Err(TryRecvError::Empty) = job.rx.try_recv() {
+                return;
+            }
+            self.cancelled = None;
+            if let Some(announce) = self.queued_scan.take() {
+                self.start_scan(announce);
+            }
+        }
+
+        let Some(job) = &self.scan else {
+            return;
+        };
+        let outcome = match job.rx.try_recv() {
+            Ok(outcome) => outcome,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => ScanOutcome::Local(Err(ScanError::SocketTable(
+                "scan thread panicked".to_string(),
+            ))),
+        };
+        let announce = job.announce;
+        self.scan = None;
+        self.finish_scan(outcome, announce);
+    }
+
+    /// Block until the background scan finishes
+    #[cfg(test)]
+    pub fn wait_for_scan(&mut self) {
+        if let Some(job) = &self.restart {
+            let result = job
+                .rx
+                .recv()
+                .unwrap_or_else(|_| Err(io::Error::other("restart thread panicked")));
+            self.finish_restart(result);
+        }
+        if let Some(job) = self.cancelled.take() {
+            let _ = job.rx.recv();
+            if let Some(announce) = self.queued_scan.take() {
+                self.start_scan(announce);
+            }
+        }
+        while let Some(job) = &self.scan {
+            let outcome = job.rx.recv().unwrap_or_else(|_| {
+                ScanOutcome::Local(Err(ScanError::SocketTable(
+                    "scan thread panicked".to_string(),
+                )))
+            });
+            let announce = job.announce;
+            self.scan = None;
+            self.finish_scan(outcome, announce);
+        }
+    }
+
+    fn finish_scan(&mut self, outcome: ScanOutcome, announce: bool) {
+        let result = match outcome {
+            ScanOutcome::Local(result) => result,
+            ScanOutcome::Remote(scanned) => {
+                self.apply_remote(scanned);
+                if announce {
+                    self.set_status(&format!("Refreshed - {} ports", self.ports.len()));
+                }
+                return;
+            }
+        };
+        let report = match result {
+            Ok(scanned) => {
+                self.containers = scanned.containers;
+                self.container_ids = scanned.container_ids;
+                self.units = scanned.units;
+                self.projects = scanned.projects;
+                self.orphans = scanned.orphans;
+                self.stale = scanned.stale;
+                scanned.report
             }
             Err(e) => {
                 self.error = Some(e.to_string());
-                self.set_status("Refresh failed");
+                if announce {
+                    self.set_status("Refresh failed");
+                }
+                return;
+            }
+        };
+
+        if matches!(self.source, Source::Diff { .. }) {
+            self.container_ids.clear();
+            self.units.clear();
+            self.projects.clear();
+            self.orphans.clear();
+            self.stale.clear();
+            self.warnings = report.warnings;
+            self.error = None;
+            self.load_diff(Some(report.ports));
+            if announce {
+                self.set_status(&format!("Refreshed - {} differences", self.diff_count()));
+            }
+        } else {
+            self.apply_report(report);
+            self.elevated = false;
+            if announce {
+                self.set_status(&format!("Refreshed - {} ports", self.ports.len()));
+            }
+        }
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan.is_some()
+    }
+
+    /// Current spinner frame while a scan is running
+    pub fn spinner(&self) -> Option<char> {
+        self.scan.as_ref().map(|job| spinner_frame(job.started))
+    }
+
+    /// Spinner frame and process name while a restart is running
+    pub fn restarting(&self) -> Option<(char, &str)> {
+        let job = self.restart.as_ref()?;
+        Some((spinner_frame(job.started), &job.name))
+    }
+
+    /// Stop waiting for the running scan, keeping the rows shown so far.
+    /// The worker stops before its next stage, and the next scan starts
+    /// once it has.
+    pub fn cancel_scan(&mut self) {
+        if self.stop_scan() {
+            self.set_status("Scan cancelled");
+        }
+    }
+
+    /// Have the running scan's worker stop, and drop its result
+    fn stop_scan(&mut self) -> bool {
+        let Some(job) = self.scan.take() else {
+            return false;
+        };
+        job.cancel.store(true, Ordering::Relaxed);
+        self.cancelled = Some(job);
+        true
+    }
+
+    fn apply_report(&mut self, report: ScanReport) {
+        self.ports = report.ports;
+        self.unix_sockets = report.unix_sockets;
+        self.warnings = report.warnings;
+        self.error = None;
+        self.rows_changed();
+    }
+
+    /// Whether actions on this machine can be retried through sudo/pkexec
+    pub fn can_elevate(&self) -> bool {
+        matches!(self.source, Source::Live) && !nix::unistd::geteuid().is_root()
+    }
+
+    /// Ask the user to confirm running `elevation` as root
+    pub fn offer_elevation(&mut self, elevation: Elevation) {
+        if self.can_elevate() {
+            self.elevation_prompt = Some(elevation);
+        } else if nix::unistd::geteuid().is_root() {
+            self.set_status("Already running as root");
+        } else {
+            self.set_status("Only live scans of this machine can be elevated");
+        }
+    }
+
+    pub fn confirm_elevation(&mut self) {
+        self.pending_elevation = self.elevation_prompt.take();
+    }
+
+    pub fn dismiss_elevation(&mut self) {
+        self.elevation_prompt = None;
+    }
+
+    /// Show the results of a scan run as root until the next refresh
+    pub fn apply_elevated_scan(&mut self, report: ScanReport) {
+        // A slower unprivileged scan must not overwrite these results
+        self.stop_scan();
+        self.container_ids = self.backend.container_ids(&report.ports);
+        self.units = self.backend.units(&report.ports);
+        self.projects = self.backend.projects(&report.ports);
+        let detached = self.backend.detached(&report.ports);
+        self.orphans = find_orphans(&detached, &self.units, &self.projects);
+        self.stale = self.backend.stale_binaries(&report.ports);
+        self.apply_report(report);
+        self.elevated = true;
+        self.set_status(&format!("Rescanned as root - {} ports", self.ports.len()));
+    }
+
+    /// Recompute the diff. When comparing against the live state, `live`
+    /// holds the ports of a finished scan, and `None` starts one instead.
+    fn load_diff(&mut self, live: Option<Vec<PortInfo>>) {
+        let Source::Diff { baseline, against } = &self.source else {
+            return;
+        };
+
+        let entries = match (against, live) {
+            (Some(snapshot), _) => snapshot::diff(&baseline.ports, &snapshot.ports),
+            (None, Some(live)) => snapshot::diff(&baseline.ports, &live),
+            (None, None) => {
+                self.start_scan(false);
+                return;
+            }
+        };
+
+        self.ports = entries.iter().map(|e| e.port_info().clone()).collect();
+        self.diff = entries;
+        self.rows_changed();
+    }
+
+    /// Show the ports of the agents that answered and report the ones that
+    /// did not
+    fn apply_remote(&mut self, scanned: RemoteScanned) {
+        if let Source::Remote { names, .. } = &mut self.source {
+            *names = scanned.names;
+        }
+        self.warnings = scanned.warnings;
+        (self.hosts, self.ports) = scanned.rows.into_iter().unzip();
+        self.error = (!scanned.errors.is_empty()).then(|| scanned.errors.join("; "));
+        self.rows_changed();
+    }
+
+    /// Name of the agent owning row `index` when showing remote ports
+    pub fn host_name(&self, index: usize) -> Option<String> {
+        let Source::Remote { names, .. } = &self.source else {
+            return None;
+        };
+        self.hosts.get(index).map(|&agent| names[agent].clone())
+    }
+
+    /// Agent owning the selected row when showing remote ports
+    pub fn get_selected_agent(&self) -> Option<usize> {
+        if !matches!(self.source, Source::Remote { .. }) {
+            return None;
+        }
+        self.selected_port_index()
+            .and_then(|i| self.hosts.get(i).copied())
+    }
+
+    /// Ask an agent to signal one of its processes
+    pub fn remote_kill(&mut self, agent: usize, pid: u32, force: bool) -> Result<(), String> {
+        match &self.source {
+            // A scan holds each agent until it answers or times out
+            Source::Remote { agents, .. } => match agents[agent].try_lock() {
+                Ok(mut client) => client.kill(pid, force),
+                Err(_) => Err("the agent is busy with a scan, try again shortly".to_string()),
+            },
+            _ => Err("not connected to any agent".to_string()),
+        }
+    }
+
+    /// Signal `pid`, through its agent for remote rows. Local kills denied
+    /// for lack of permission offer to retry as root.
+    pub fn kill_process(&mut self, pid: u32, force: bool) {
+        let result = match self.get_selected_agent() {
+            Some(agent) => self.remote_kill(agent, pid, force),
+            None => match self.backend.kill(pid, force) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied && self.can_elevate() => {
+                    self.offer_elevation(Elevation::Kill {
+                        pids: vec![pid],
+                        force,
+                    });
+                    return;
+                }
+                result => result.map_err(|e| e.to_string()),
+            },
+        };
+
+        match result {
+            Ok(()) => self.killed(&[pid], force),
+            Err(e) => self.set_status(&format!("Failed: {e}")),
+        }
+    }
+
+    /// Signal each of `pids` through the backend. Returns the errors, and
+    /// separately the PIDs denied for lack of permission when those can be
+    /// retried as root.
+    fn kill_each(&self, pids: &[u32], force: bool) -> (Vec<String>, Vec<u32>) {
+        let can_elevate = self.can_elevate();
+        let mut errors = Vec::new();
+        let mut denied = Vec::new();
+        for &pid in pids {
+            match self.backend.kill(pid, force) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied && can_elevate => {
+                    denied.push(pid)
+                }
+                Err(e) => errors.push(format!("PID {pid}: {e}")),
+            }
+        }
+        (errors, denied)
+    }
+
+    /// Rescan after `pids` were signalled and say so
+    pub fn killed(&mut self, pids: &[u32], force: bool) {
+        self.start_scan(false);
+        let verb = if force { "Force killed" } else { "Terminated" };
+        match pids {
+            [pid] => self.set_status(&format!("{verb} PID {pid}")),
+            _ => self.set_status(&format!("{verb} {} processes", pids.len())),
+        }
+    }
+
+    /// Stop or restart the container behind the selected row, then rescan
+    pub fn container_action(&mut self, action: ContainerAction) {
+        let Some(container) = self.get_selected_container().cloned() else {
+            self.set_status("The selected listener does not belong to a container");
+            return;
+        };
+        match self.backend.container_action(&container.id, action) {
+            Ok(()) => {
+                self.start_scan(false);
+                self.set_status(&format!("{} container {}", action.done(), container.name));
+            }
+            Err(e) => self.set_status(&format!("Failed: {e}")),
+        }
+    }
+
+    /// Stop or restart the selected row's systemd unit, then rescan
+    pub fn unit_action(&mut self, action: UnitAction) {
+        let Some(unit) = self.get_selected_unit().cloned() else {
+            self.set_status("The selected listener does not belong to a systemd unit");
+            return;
+        };
+        match self.backend.unit_action(&unit, action) {
+            Ok(()) => {
+                self.start_scan(false);
+                self.set_status(&format!("{} {}", action.done(), unit.name));
+            }
+            Err(e) => self.set_status(&format!("Failed: {e}")),
+        }
+    }
+
+    /// Ask the event loop to show `systemctl status` for the selected row's
+    /// unit with the TUI suspended
+    pub fn show_unit_status(&mut self) {
+        match self.get_selected_unit() {
+            Some(unit) => self.pending_unit_status = Some(unit.clone()),
+            None => self.set_status("The selected listener does not belong to a systemd unit"),
+        }
+    }
+
+    fn diff_count(&self) -> usize {
+        self.diff
+            .iter()
+            .filter(|e| e.kind != snapshot::DiffKind::Unchanged)
+            .count()
+    }
+
+    /// Move the timeline cursor by `steps` of the current step size
+    pub fn timeline_step(&mut self, steps: i64) {
+        if let Source::Timeline { at, step, .. } = &mut self.source {
+            let delta = TIMELINE_STEPS[*step].0.saturating_mul(steps.unsigned_abs());
+            *at = if steps < 0 {
+                at.saturating_sub(delta)
+            } else {
+                at.saturating_add(delta).min(snapshot::unix_now())
+            };
+        }
+        self.load_timeline();
+    }
+
+    /// Jump the timeline cursor to the previous or next recorded event
+    pub fn timeline_jump(&mut self, forward: bool) {
+        if let Source::Timeline { history, at, .. } = &mut self.source {
+            let target = if forward {
+                history.next_event(*at)
+            } else {
+                history.prev_event(*at)
+            };
+            match target {
+                Some(t) => *at = t,
+                None => {
+                    self.set_status("No more events");
+                    return;
+                }
+            }
+        }
+        self.load_timeline();
+    }
+
+    /// Cycle through the timeline step sizes
+    pub fn timeline_zoom(&mut self, coarser: bool) {
+        if let Source::Timeline { step, .. } = &mut self.source {
+            *step = if coarser {
+                (*step + 1).min(TIMELINE_STEPS.len() - 1)
+            } else {
+                step.saturating_sub(1)
+            };
+        }
+    }
+
+    fn load_timeline(&mut self) {
+        if let Source::Timeline { history, at, .. } = &self.source {
+            self.ports = history.state_at(*at);
+            self.rows_changed();
+        }
+    }
+
+    /// Save the current rows to a timestamped snapshot file in the working directory
+    pub fn save_snapshot(&mut self) {
+        if matches!(self.source, Source::Diff { .. }) {
+            self.set_status("Cannot save a diff as a snapshot");
+            return;
+        }
+        // Snapshots of a past moment keep the time they describe
+        let taken_at = match &self.source {
+            Source::Timeline { at, .. } => Some(*at),
+            _ => None,
+        };
+        let path = PathBuf::from(format!("port-checker-{}.json", snapshot::unix_now()));
+        let mut snapshot = Snapshot::new(self.ports.clone());
+        if let Some(at) = taken_at {
+            snapshot.taken_at = at;
+        }
+        match snapshot.save(&path) {
+            Ok(()) => self.set_status(&format!("Saved {}", path.display())),
+            Err(e) => self.set_status(&format!("Save failed: {e}")),
+        }
+    }
+
+    /// One line per kind of scan warning, for the status area
+    pub fn warning_summary(&self) -> Vec<String> {
+        // Hidden sockets of the other view would only confuse
+        let warnings: Vec<ScanWarning> = self
+            .warnings
+            .iter()
+            .filter(|w| match w {
+                ScanWarning::HiddenSocket { .. } => self.view == View::Tcp,
+                ScanWarning::HiddenUnixSocket { .. } => self.view == View::Unix,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        ports::summarize_warnings(&warnings)
+    }
+
+    /// Whether the selected row's process details were hidden from the scan
+    pub fn is_selected_restricted(&self) -> bool {
+        self.get_selected_pid().is_some_and(|pid| {
+            self.warnings
+                .contains(&ScanWarning::RestrictedProcess { pid })
+        })
+    }
+
+    /// Diff entry for the selected row, when showing a diff
+    pub fn get_selected_diff(&self) -> Option<&DiffEntry> {
+        self.selected_port_index().and_then(|i| self.diff.get(i))
+    }
+
+    /// Rebuild the display strings and keep the selection in bounds after
+    /// `ports` (and `hosts`) change
+    fn rows_changed(&mut self) {
+        let show_path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let mine = |uid: Option<u32>| !self.mine_only || uid == Some(self.uid);
+        self.shown = match self.view {
+            View::Tcp => (0..self.ports.len())
+                .filter(|&i| {
+                    let p = &self.ports[i];
+                    let orphan = !self.orphans_only || self.is_orphan(i);
+                    self.namespace_filter.matches(p) && mine(p.uid) && orphan
+                })
+                .collect(),
+            View::Unix => (0..self.unix_sockets.len())
+                .filter(|&i| mine(self.unix_sockets[i].uid))
+                .collect(),
+        };
+        self.namespace_column = self.ports.iter().any(|p| p.namespace.is_some());
+        self.unit_column = self.units.iter().any(Option::is_some);
+        self.project_column = self.projects.iter().any(Option::is_some);
+        self.flags_column = (0..self.ports.len()).any(|i| !self.flags(i).is_empty());
+        self.rows = match self.view {
+            View::Tcp => self
+                .shown
+                .iter()
+                .map(|&i| {
+                    let p = &self.ports[i];
+                    let process = match docker::find(&self.containers, p, self.container_id(i)) {
+                        Some(container) => format!("{} ({})", container.name, p.process_name),
+                        None => process_label(p),
+                    };
+                    let mut cells = vec![
+                        p.port.to_string(),
+                        p.address.to_string(),
+                        p.pid.to_string(),
+                        user_label(p.uid, p.user.as_deref(), false),
+                        process,
+                    ];
+                    if self.project_column {
+                        let project = self.projects.get(i).and_then(Option::as_ref);
+                        cells.push(project.map_or("-", |p| &p.name).to_string());
+                        let branch = project.and_then(|p| p.branch.as_deref());
+                        cells.push(branch.unwrap_or("-").to_string());
+                    }
+                    if self.unit_column {
+                        let unit = self.units.get(i).and_then(Option::as_ref);
+                        cells.push(unit.map_or("-", |u| &u.name).to_string());
+                    }
+                    if self.flags_column {
+                        cells.push(self.flags(i).join(", "));
+                    }
+                    if self.namespace_column {
+                        let namespace = p.namespace.as_ref().map_or("host", |ns| &ns.name);
+                        cells.insert(0, namespace.to_string());
+                    }
+                    if let Some(host) = self.host_name(i) {
+                        cells.insert(0, host);
+                    }
+                    RowText {
+                        cells,
+                        scrolled: show_path(&p.exe_path),
+                        owner: Owner::of(p.uid, self.uid),
+                        group: None,
+                    }
+                })
+                .collect(),
+            View::Unix => self
+                .shown
+                .iter()
+                .map(|&i| {
+                    let u = &self.unix_sockets[i];
+                    RowText {
+                        cells: vec![
+                            u.pid.to_string(),
+                            user_label(u.uid, u.user.as_deref(), false),
+                            u.process_name.clone(),
+                            show_path(&u.cwd),
+                        ],
+                        scrolled: u.path.clone(),
+                        owner: Owner::of(u.uid, self.uid),
+                        group: None,
+                    }
+                })
+                .collect(),
+        };
+        if self.grouping != Grouping::None && self.view == View::Tcp {
+            self.group_rows();
+        }
+        self.adjust_selection();
+    }
+
+    fn group_key(&self, port: usize) -> GroupKey {
+        match self.grouping {
+            Grouping::Project => GroupKey::Project(
+                self.projects
+                    .get(port)
+                    .and_then(Option::as_ref)
+                    .map(|p| p.root.clone()),
+            ),
+            _ => GroupKey::Process(self.hosts.get(port).copied(), self.ports[port].pid),
+        }
+    }
+
+    /// Replace the flat TCP rows with one row per process or project, in
+    /// order of their lowest port, each followed by its ports when
+    /// expanded. Ports outside any project come last.
+    fn group_rows(&mut self) {
+        // Port and address columns come after the host and namespace ones
+        let lead = usize::from(matches!(self.source, Source::Remote { .. }))
+            + usize::from(self.namespace_column);
+
+        let mut order = Vec::new();
+        let mut groups: HashMap<GroupKey, Vec<(usize, RowText)>> = HashMap::new();
+        for (&i, row) in self.shown.iter().zip(std::mem::take(&mut self.rows)) {
+            let key = self.group_key(i);
+            let group = groups.entry(key.clone()).or_default();
+            if group.is_empty() {
+                order.push(key);
+            }
+            group.push((i, row));
+        }
+        order.sort_by_key(|key| *key == GroupKey::Project(None));
+
+        let mut shown = Vec::with_capacity(self.shown.len());
+        for key in order {
+            let members = groups.remove(&key).unwrap_or_default();
+            let ports: Vec<usize> = members.iter().map(|&(i, _)| i).collect();
+            let expanded = self.expanded.contains(&key);
+
+            let first = &members[0].1;
+            let mut cells = first.cells.clone();
+            let marker = if expanded { '\u{25be}' } else { '\u{25b8}' };
+            cells[lead] = match ports.len() {
+                1 => format!("{marker} {}", cells[lead]),
+                n => format!("{marker} {n} ports"),
+            };
+            let address = &self.ports[ports[0]].address;
+            if ports.iter().any(|&i| self.ports[i].address != *address) {
+                cells[lead + 1] = "(several)".to_string();
+            }
+            let mut pids: Vec<u32> = ports.iter().map(|&i| self.ports[i].pid).collect();
+            pids.sort_unstable();
+            pids.dedup();
+            if pids.len() > 1 {
+                // A project row stands for several processes
+                let uid = self.ports[ports[0]].uid;
+                let mut names: Vec<&str> = Vec::new();
+                for &i in &ports {
+                    let name = self.ports[i].process_name.as_str();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                cells[lead + 2] = format!("{} pids", pids.len());
+                if ports.iter().any(|&i| self.ports[i].uid != uid) {
+                    cells[lead + 3] = "(several)".to_string();
+                }
+                cells[lead + 4] = names.join(", ");
+            }
+            self.rows.push(RowText {
+                cells,
+                scrolled: first.scrolled.clone(),
+                owner: first.owner,
+                group: Some(ports.clone()),
+            });
+            shown.push(ports[0]);
+
+            if expanded {
+                for (i, mut row) in members {
+                    row.cells[lead] = format!("  \u{2514} {}", row.cells[lead]);
+                    self.rows.push(row);
+                    shown.push(i);
+                }
+            }
+        }
+        self.shown = shown;
+    }
+
+    /// Cycle the TCP table through one row per port, grouped by process
+    /// and, when some listeners run from a project, grouped by project
+    pub fn cycle_grouping(&mut self) {
+        if self.view != View::Tcp {
+            self.set_status("Only TCP ports can be grouped");
+            return;
+        }
+        self.grouping = match self.grouping {
+            Grouping::None => Grouping::Process,
+            Grouping::Process if self.project_column => Grouping::Project,
+            Grouping::Process | Grouping::Project => Grouping::None,
+        };
+        self.table_state = TableState::default();
+        self.rows_changed();
+        self.set_status(match self.grouping {
+            Grouping::None => "One row per port",
+            Grouping::Process => "Grouped by process - Space expands a process",
+            Grouping::Project => "Grouped by project - Space expands a project",
+        });
+    }
+
+    /// Expand or collapse the selected group of the grouped view. On a
+    /// port row, collapse the group it belongs to.
+    pub fn toggle_group(&mut self) {
+        let Some(selected) = self.table_state.selected() else {
+            return;
+        };
+        if self.grouping == Grouping::None || self.view != View::Tcp {
+            return;
+        }
+        let Some(&port) = self.shown.get(selected) else {
+            return;
+        };
+        let key = self.group_key(port);
+        let on_group = self.rows[selected].group.is_some();
+        if on_group && !self.expanded.remove(&key) {
+            self.expanded.insert(key.clone());
+        } else if !on_group {
+            self.expanded.remove(&key);
+        }
+        self.rows_changed();
+
+        let row = (0..self.rows.len())
+            .find(|&r| self.rows[r].group.is_some() && self.group_key(self.shown[r]) == key);
+        self.table_state.select(row.or(Some(selected)));
+        self.adjust_selection();
+    }
+
+    /// Every port of the selected process or project row of the grouped view
+    pub fn get_selected_group(&self) -> Option<&[usize]> {
+        let selected = self.table_state.selected()?;
+        self.rows.get(selected)?.group.as_deref()
+    }
+
+    /// Project the selected row's process runs from
+    pub fn get_selected_project(&self) -> Option<&Project> {
+        self.projects.get(self.selected_port_index()?)?.as_ref()
+    }
+
+    /// Project of the selected row when it is a project row of the grouped
+    /// view, whose actions apply to all of the project's processes
+    pub fn get_selected_project_group(&self) -> Option<&Project> {
+        if self.grouping != Grouping::Project {
+            return None;
+        }
+        self.get_selected_group()?;
+        self.get_selected_project()
+    }
+
+    /// Processes of the selected project row, in PID order
+    pub fn project_pids(&self) -> Vec<u32> {
+        let mut pids: Vec<u32> = match self.get_selected_project_group() {
+            Some(_) => self
+                .get_selected_group()
+                .unwrap_or_default()
+                .iter()
+                .map(|&i| self.ports[i].pid)
+                .collect(),
+            None => vec![],
+        };
+        pids.sort_unstable();
+        pids.dedup();
+        pids
+    }
+
+    /// Terminate or kill every process running from the selected project,
+    /// then rescan
+    pub fn kill_project(&mut self, force: bool) {
+        let Some(name) = self.get_selected_project_group().map(|p| p.name.clone()) else {
+            self.set_status("Select a project row of the by-project view first");
+            return;
+        };
+        let pids = self.project_pids();
+        let (errors, denied) = self.kill_each(&pids, force);
+        self.start_scan(false);
+
+        let verb = if force { "Force killed" } else { "Terminated" };
+        let done = pids.len() - errors.len() - denied.len();
+        let plural = if pids.len() == 1 { "" } else { "es" };
+        let mut status = if done == pids.len() {
+            format!("{verb} {done} process{plural} of {name}")
+        } else {
+            format!("{verb} {done} of {} process{plural} of {name}", pids.len())
+        };
+        if let Some(error) = errors.first() {
+            status.push_str(&format!(" - {error}"));
+        }
+        self.set_status(&status);
+        // One prompt retries every process that was denied
+        if !denied.is_empty() {
+            self.offer_elevation(Elevation::Kill {
+                pids: denied,
+                force,
+            });
+        }
+    }
+
+    /// Show the next network namespace: all, the host's, then each other
+    /// one found by the last scan
+    pub fn cycle_namespace(&mut self) {
+        if !matches!(self.source, Source::Live) || self.view != View::Tcp {
+            self.set_status("Namespaces can only be switched in the live TCP view");
+            return;
+        }
+        let mut namespaces: Vec<&Namespace> = self
+            .ports
+            .iter()
+            .filter_map(|p| p.namespace.as_ref())
+            .collect();
+        if namespaces.is_empty() && self.namespace_filter == NamespaceFilter::All {
+            self.set_status(if self.scans_namespaces {
+                "No other network namespaces found"
+            } else {
+                "Other network namespaces are only scanned with --namespaces"
+            });
+            return;
+        }
+        namespaces.sort_by(|a, b| (&a.name, a.inode).cmp(&(&b.name, b.inode)));
+        namespaces.dedup();
+
+        let mut filters = vec![NamespaceFilter::All, NamespaceFilter::Host];
+        filters.extend(namespaces.into_iter().cloned().map(NamespaceFilter::Only));
+        let next = filters
+            .iter()
+            .position(|f| *f == self.namespace_filter)
+            .map_or(0, |i| (i + 1) % filters.len());
+        self.namespace_filter = filters.swap_remove(next);
+
+        self.table_state = TableState::default();
+        self.rows_changed();
+        self.set_status(&format!("Namespace: {}", self.namespace_filter.label()));
+    }
+
+    pub fn is_orphan(&self, port: usize) -> bool {
+        self.orphans.get(port).copied().unwrap_or(false)
+    }
+
+    /// Problems worth a look on row `port` of `ports`, for the flags column
+    pub fn flags(&self, port: usize) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if self.is_orphan(port) {
+            flags.push("orphan");
+        }
+        if self.is_stale(port) {
+            flags.push("stale binary");
+        }
+        flags
+    }
+
+    pub fn is_stale(&self, port: usize) -> bool {
+        self.stale.get(port).copied().unwrap_or(false)
+    }
+
+    /// Whether the selected row's process runs a stale binary
+    pub fn is_selected_stale(&self) -> bool {
+        self.selected_port_index().is_some_and(|i| self.is_stale(i))
+    }
+
+    /// Restart the selected row's process from its rebuilt binary on a
+    /// worker thread, as waiting for the old process to exit takes a while.
+    /// `poll_scan` rescans once it is done.
+    pub fn restart_process(&mut self) {
+        if !self.is_selected_stale() {
+            self.set_status("The selected listener runs an up-to-date binary");
+            return;
+        }
+        if self.restart.is_some() {
+            self.set_status("Another restart is still running");
+            return;
+        }
+        let Some(port) = self.get_selected_port().cloned() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let backend = Arc::clone(&self.backend);
+        let (name, pid) = (port.process_name.clone(), port.pid);
+        thread::spawn(move || {
+            let _ = tx.send(backend.restart(&port));
+        });
+        self.restart = Some(RestartJob {
+            name,
+            pid,
+            rx,
+            started: Instant::now(),
+        });
+    }
+
+    fn finish_restart(&mut self, result: io::Result<u32>) {
+        let Some(job) = self.restart.take() else {
+            return;
+        };
+        match result {
+            Ok(pid) => {
+                self.start_scan(false);
+                self.set_status(&format!(
+                    "Restarted {} (PID {} -> {pid})",
+                    job.name, job.pid
+                ));
+            }
+            Err(e) => self.set_status(&format!("Failed: {e}")),
+        }
+    }
+
+    /// Show only the listeners of orphaned dev servers, or all again
+    pub fn toggle_orphans(&mut self) {
+        if !self.orphans_only && !self.orphans.contains(&true) {
+            self.set_status("No orphaned dev servers found");
+            return;
+        }
+        self.orphans_only = !self.orphans_only;
+        self.table_state = TableState::default();
+        self.rows_changed();
+        self.set_status(if self.orphans_only {
+            "Showing only orphaned dev servers - O cleans them up"
+        } else {
+            "Showing all listeners"
+        });
+    }
+
+    /// Ask to terminate every orphaned dev server at once
+    pub fn offer_cleanup(&mut self) {
+        if !matches!(self.source, Source::Live) {
+            self.set_status("Orphans are only detected in the live view");
+            return;
+        }
+        let mut servers: Vec<(u32, String)> = Vec::new();
+        for (i, p) in self.ports.iter().enumerate() {
+            if !self.is_orphan(i) || servers.iter().any(|(pid, _)| *pid == p.pid) {
+                continue;
+            }
+            let mut description = process_label(p);
+            if let Some(project) = self.projects.get(i).and_then(Option::as_ref) {
+                description.push_str(&format!(" in {}", project.name));
+            }
+            servers.push((p.pid, description));
+        }
+        if servers.is_empty() {
+            self.set_status("No orphaned dev servers found");
+        } else {
+            self.cleanup_prompt = Some(servers);
+        }
+    }
+
+    /// Terminate the orphans listed in the cleanup prompt, then rescan
+    pub fn confirm_cleanup(&mut self) {
+        let Some(servers) = self.cleanup_prompt.take() else {
+            return;
+        };
+        let pids: Vec<u32> = servers.iter().map(|&(pid, _)| pid).collect();
+        let (errors, denied) = self.kill_each(&pids, false);
+        self.start_scan(false);
+
+        let done = pids.len() - errors.len() - denied.len();
+        let plural = if pids.len() == 1 { "" } else { "s" };
+        let mut status = if done == pids.len() {
+            format!("Terminated {done} orphaned dev server{plural}")
+        } else {
+            format!(
+                "Terminated {done} of {} orphaned dev server{plural}",
+                pids.len()
+            )
+        };
+        if let Some(error) = errors.first() {
+            status.push_str(&format!(" - {error}"));
+        }
+        self.set_status(&status);
+        if !denied.is_empty() {
+            self.offer_elevation(Elevation::Kill {
+                pids: denied,
+                force: false,
+            });
+        }
+    }
+
+    pub fn dismiss_cleanup(&mut self) {
+        self.cleanup_prompt = None;
+    }
+
+    /// Show only the current user's listeners, or everyone's again
+    pub fn toggle_mine(&mut self) {
+        self.mine_only = !self.mine_only;
+        self.table_state = TableState::default();
+        self.rows_changed();
+        self.set_status(if self.mine_only {
+            "Showing only your processes"
+        } else {
+            "Showing all users' processes"
+        });
+    }
+
+    /// Switch between the TCP and Unix socket tables, scanning for Unix
+    /// sockets on the way in since they are not listed otherwise
+    pub fn toggle_view(&mut self) {
+        if !matches!(self.source, Source::Live) {
+            self.set_status("Unix sockets are only listed in the live view");
+            return;
+        }
+        self.view = match self.view {
+            View::Tcp => View::Unix,
+            View::Unix => View::Tcp,
+        };
+        self.table_state = TableState::default();
+        self.scroll_offset = 0;
+        self.rows_changed();
+        if self.view == View::Unix {
+            // A scan started in the TCP view would come back without them
+            let announce = self.scan.as_ref().is_some_and(|job| job.announce);
+            if self.stop_scan() {
+                self.queued_scan = Some(self.queued_scan.unwrap_or(false) | announce);
+            } else {
+                self.start_scan(false);
             }
         }
     }
@@ -76,14 +1555,14 @@ impl App {
     /// Adjust selection to stay within bounds after port list changes
     fn adjust_selection(&mut self) {
         if let Some(selected) = self.table_state.selected() {
-            if selected >= self.ports.len() {
-                self.table_state.select(if self.ports.is_empty() {
+            if selected >= self.rows.len() {
+                self.table_state.select(if self.rows.is_empty() {
                     None
                 } else {
-                    Some(self.ports.len() - 1)
+                    Some(self.rows.len() - 1)
                 });
             }
-        } else if !self.ports.is_empty() {
+        } else if !self.rows.is_empty() {
             self.table_state.select(Some(0));
         }
     }
@@ -103,18 +1582,18 @@ impl App {
     }
 
     pub fn next(&mut self) {
-        if self.ports.is_empty() {
+        if self.rows.is_empty() {
             return;
         }
         let i = match self.table_state.selected() {
-            Some(i) => (i + 1).min(self.ports.len() - 1),
+            Some(i) => (i + 1).min(self.rows.len() - 1),
             None => 0,
         };
         self.table_state.select(Some(i));
     }
 
     pub fn previous(&mut self) {
-        if self.ports.is_empty() {
+        if self.rows.is_empty() {
             return;
         }
         let i = match self.table_state.selected() {
@@ -133,47 +1612,979 @@ impl App {
     }
 
     pub fn open_terminate_popup(&mut self) {
-        if self.table_state.selected().is_some() && !self.ports.is_empty() {
+        if self.table_state.selected().is_some() && !self.rows.is_empty() {
             self.show_terminate_popup = true;
             self.popup_selection = PopupButton::default();
+            // Only this machine's processes can be looked up, and only now
+            self.tree = match self.source {
+                Source::Live => self
+                    .get_selected_pid()
+                    .and_then(|pid| self.backend.process_tree(pid)),
+                _ => None,
+            };
+            self.tree_cursor = self.tree.as_ref().map_or(0, ProcessTree::process_index);
         }
     }
 
+    /// Move the process tree highlight down
+    pub fn tree_next(&mut self) {
+        if let Some(tree) = &self.tree {
+            self.tree_cursor = (self.tree_cursor + 1).min(tree.entries().len() - 1);
+        }
+    }
+
+    /// Move the process tree highlight up
+    pub fn tree_prev(&mut self) {
+        self.tree_cursor = self.tree_cursor.saturating_sub(1);
+    }
+
+    /// Highlighted process of the popup's tree
+    pub fn tree_selection(&self) -> Option<&ProcEntry> {
+        let tree = self.tree.as_ref()?;
+        tree.entries()
+            .get(self.tree_cursor)
+            .map(|&(_, entry)| entry)
+    }
+
     pub fn close_popup(&mut self) {
         self.show_terminate_popup = false;
     }
 
+    /// Buttons of the popup in tab order; container actions only when the
+    /// selected row belongs to one
+    pub fn popup_buttons(&self) -> Vec<PopupButton> {
+        let mut buttons = vec![
+            PopupButton::Cancel,
+            PopupButton::Terminate,
+            PopupButton::ForceKill,
+        ];
+        // Terminate and kill act on the whole project, which may hold
+        // several containers and units
+        if self.get_selected_project_group().is_some() {
+            return buttons;
+        }
+        if self.get_selected_container().is_some() {
+            buttons.extend([PopupButton::StopContainer, PopupButton::RestartContainer]);
+        } else if self.get_selected_unit().is_some() {
+            buttons.extend([
+                PopupButton::StopUnit,
+                PopupButton::RestartUnit,
+                PopupButton::UnitStatus,
+            ]);
+        } else if self.is_selected_stale() {
+            buttons.push(PopupButton::RestartProcess);
+        }
+        buttons
+    }
+
     pub fn popup_next(&mut self) {
-        self.popup_selection = match self.popup_selection {
-            PopupButton::Cancel => PopupButton::Terminate,
-            PopupButton::Terminate => PopupButton::ForceKill,
-            PopupButton::ForceKill => PopupButton::Cancel,
-        };
+        let buttons = self.popup_buttons();
+        let i = buttons
+            .iter()
+            .position(|&b| b == self.popup_selection)
+            .unwrap_or(0);
+        self.popup_selection = buttons[(i + 1) % buttons.len()];
     }
 
     pub fn popup_prev(&mut self) {
-        self.popup_selection = match self.popup_selection {
-            PopupButton::Cancel => PopupButton::ForceKill,
-            PopupButton::Terminate => PopupButton::Cancel,
-            PopupButton::ForceKill => PopupButton::Terminate,
-        };
+        let buttons = self.popup_buttons();
+        let i = buttons
+            .iter()
+            .position(|&b| b == self.popup_selection)
+            .unwrap_or(0);
+        self.popup_selection = buttons[(i + buttons.len() - 1) % buttons.len()];
+    }
+
+    /// Index into `ports` of the selected row of the TCP view
+    pub fn selected_port_index(&self) -> Option<usize> {
+        if self.view != View::Tcp {
+            return None;
+        }
+        self.table_state
+            .selected()
+            .and_then(|i| self.shown.get(i).copied())
     }
 
     pub fn get_selected_port(&self) -> Option<&PortInfo> {
-        self.table_state.selected().and_then(|i| self.ports.get(i))
+        self.selected_port_index().and_then(|i| self.ports.get(i))
+    }
+
+    pub fn get_selected_unix(&self) -> Option<&UnixSocketInfo> {
+        if self.view != View::Unix {
+            return None;
+        }
+        self.table_state
+            .selected()
+            .and_then(|i| self.unix_sockets.get(*self.shown.get(i)?))
+    }
+
+    /// Container behind the selected row, in the live TCP view
+    pub fn get_selected_container(&self) -> Option<&Container> {
+        if !matches!(self.source, Source::Live) {
+            return None;
+        }
+        let index = self.selected_port_index()?;
+        let port = self.ports.get(index)?;
+        docker::find(&self.containers, port, self.container_id(index))
+    }
+
+    /// ID of the container row `port` of `ports` runs in, by its cgroup
+    fn container_id(&self, port: usize) -> Option<&str> {
+        self.container_ids.get(port)?.as_deref()
+    }
+
+    /// systemd unit of the selected row, in the live TCP view
+    pub fn get_selected_unit(&self) -> Option<&Unit> {
+        if !matches!(self.source, Source::Live) {
+            return None;
+        }
+        self.units.get(self.selected_port_index()?)?.as_ref()
     }
 
+    /// Process owning the selected row, in either view
+    pub fn get_selected_pid(&self) -> Option<u32> {
+        self.get_selected_port()
+            .map(|p| p.pid)
+            .or_else(|| self.get_selected_unix().map(|u| u.pid))
+    }
+
+    /// Run the selected popup button. Container and unit actions are taken here;
+    /// signals are returned as (pid, force) for the caller to send.
     pub fn execute_popup_action(&mut self) -> Option<(u32, bool)> {
-        let result = self.get_selected_port().and_then(|p| {
-            let pid = p.pid;
+        if self.is_read_only() {
+            self.close_popup();
+            return None;
+        }
+
+        if self.get_selected_project_group().is_some() {
             match self.popup_selection {
+                PopupButton::Terminate => self.kill_project(false),
+                PopupButton::ForceKill => self.kill_project(true),
+                _ => {}
+            }
+            self.close_popup();
+            return None;
+        }
+
+        let result = self
+            .get_selected_pid()
+            .and_then(|pid| match self.popup_selection {
                 PopupButton::Cancel => None,
                 PopupButton::Terminate => Some((pid, false)),
                 PopupButton::ForceKill => Some((pid, true)),
-            }
-        });
+                PopupButton::StopContainer => {
+                    self.container_action(ContainerAction::Stop);
+                    None
+                }
+                PopupButton::RestartContainer => {
+                    self.container_action(ContainerAction::Restart);
+                    None
+                }
+                PopupButton::StopUnit => {
+                    self.unit_action(UnitAction::Stop);
+                    None
+                }
+                PopupButton::RestartUnit => {
+                    self.unit_action(UnitAction::Restart);
+                    None
+                }
+                PopupButton::UnitStatus => {
+                    self.show_unit_status();
+                    None
+                }
+                PopupButton::RestartProcess => {
+                    self.restart_process();
+                    None
+                }
+            });
 
         self.close_popup();
         result
     }
 }
+
+/// Scan this machine, with its Unix sockets when `unix` is set, and look up
+/// the containers, units, projects and processes behind its listeners, or
+/// `None` when `cancel` is set between two of these stages. Runs on a worker
+/// thread.
+fn scan_local(backend: &dyn ScanBackend, unix: bool, cancel: &AtomicBool) -> Option<ScanResult> {
+    let go_on = || (!cancel.load(Ordering::Relaxed)).then_some(());
+    let mut report = match backend.scan(unix) {
+        Ok(report) => report,
+        Err(e) => return Some(Err(e)),
+    };
+    go_on()?;
+    let container_ids = backend.container_ids(&report.ports);
+    go_on()?;
+    // The daemon is only asked when some listener could be a container's;
+    // without Docker there are simply none
+    let containers = if report.ports.iter().any(docker::may_belong)
+        || container_ids.iter().any(Option::is_some)
+    {
+        backend.containers().unwrap_or_else(|e| {
+            report.warnings.push(ScanWarning::Containers(e.to_string()));
+            vec![]
+        })
+    } else {
+        vec![]
+    };
+    go_on()?;
+    let units = backend.units(&report.ports);
+    go_on()?;
+    let projects = backend.projects(&report.ports);
+    go_on()?;
+    let orphans = find_orphans(&backend.detached(&report.ports), &units, &projects);
+    go_on()?;
+    let stale = backend.stale_binaries(&report.ports);
+    Some(Ok(Scanned {
+        report,
+        containers,
+        container_ids,
+        units,
+        projects,
+        orphans,
+        stale,
+    }))
+}
+
+/// Scan every agent, keeping the ports of those that answered and the
+/// errors of the ones that did not, or `None` when `cancel` is set before
+/// the last agent. Runs on a worker thread.
+fn scan_agents(agents: &[Arc<Mutex<AgentClient>>], cancel: &AtomicBool) -> Option<RemoteScanned> {
+    let mut scanned = RemoteScanned {
+        rows: Vec::new(),
+        warnings: Vec::new(),
+        errors: Vec::new(),
+        names: Vec::new(),
+    };
+    for (i, agent) in agents.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut agent = agent.lock().unwrap_or_else(|e| e.into_inner());
+        match agent.scan() {
+            Ok(report) => {
+                scanned
+                    .rows
+                    .extend(report.ports.into_iter().map(|p| (i, p)));
+                scanned.warnings.extend(report.warnings);
+            }
+            Err(e) => scanned.errors.push(e),
+        }
+        scanned.names.push(agent.name());
+    }
+    scanned.rows.sort_by_key(|(i, p)| (p.port, *i));
+    Some(scanned)
+}
+
+/// Orphaned dev servers among detached processes: those that run from a
+/// project, and are not services that systemd started that way
+fn find_orphans(
+    detached: &[bool],
+    units: &[Option<Unit>],
+    projects: &[Option<Project>],
+) -> Vec<bool> {
+    detached
+        .iter()
+        .enumerate()
+        .map(|(i, &detached)| {
+            let unit = units.get(i).is_some_and(Option::is_some);
+            let project = projects.get(i).is_some_and(Option::is_some);
+            detached && project && !unit
+        })
+        .collect()
+}
+
+/// Spinner frame for a job running since `started`
+fn spinner_frame(started: Instant) -> char {
+    let elapsed = started.elapsed().as_millis();
+    SPINNER_FRAMES[(elapsed / SPINNER_FRAME_MS) as usize % SPINNER_FRAMES.len()]
+}
+
+/// Process name, with what it runs when a recognizer knows its command
+/// line, as in `next dev (node)`
+fn process_label(p: &PortInfo) -> String {
+    match recognize::label(&p.cmd_args) {
+        Some(label) if label.starts_with(&p.process_name) => label,
+        Some(label) => format!("{label} ({})", p.process_name),
+        None => p.process_name.clone(),
+    }
+}
+
+/// Owner of a process: the user name, else the UID. `with_uid` adds the
+/// UID after a known name, as in `alice (1000)`.
+pub fn user_label(uid: Option<u32>, user: Option<&str>, with_uid: bool) -> String {
+    match (user, uid) {
+        (Some(user), Some(uid)) if with_uid => format!("{user} ({uid})"),
+        (Some(user), _) => user.to_string(),
+        (None, Some(uid)) => uid.to_string(),
+        (None, None) => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+
+    fn fake_app() -> (App, FakeBackend) {
+        let backend = FakeBackend::from_fixture();
+        let mut app = App::with_backend(Box::new(backend.clone()));
+        app.wait_for_scan();
+        (app, backend)
+    }
+
+    #[test]
+    fn test_navigation_and_refresh() {
+        let (mut app, backend) = fake_app();
+        assert_eq!(app.ports.len(), 5);
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        app.previous();
+        assert_eq!(app.table_state.selected(), Some(0));
+        for _ in 0..10 {
+            app.next();
+        }
+        assert_eq!(app.table_state.selected(), Some(4));
+
+        // Rows disappearing under the cursor pull the selection back in range
+        backend.set_report(ScanReport {
+            ports: app.ports[..2].to_vec(),
+            ..Default::default()
+        });
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(backend.scans(), 2);
+        assert_eq!(app.ports.len(), 2);
+        assert_eq!(app.table_state.selected(), Some(1));
+        assert_eq!(app.status_message.as_deref(), Some("Refreshed - 2 ports"));
+    }
+
+    #[test]
+    fn test_kill_flow() {
+        let (mut app, backend) = fake_app();
+        app.next();
+        app.open_terminate_popup();
+        assert!(app.show_terminate_popup);
+        app.popup_next();
+        let (pid, force) = app.execute_popup_action().expect("terminate selected");
+        assert!(!app.show_terminate_popup);
+
+        app.kill_process(pid, force);
+        app.wait_for_scan();
+        assert_eq!(backend.kills(), vec![(4242, false)]);
+        // Both of node's listeners are gone after the rescan
+        assert_eq!(app.ports.len(), 3);
+        assert_eq!(app.status_message.as_deref(), Some("Terminated PID 4242"));
+
+        backend.fail_kills(Some(io::ErrorKind::NotFound));
+        app.kill_process(812, true);
+        assert_eq!(backend.kills().len(), 1);
+        assert!(app
+            .status_message
+            .as_deref()
+            .unwrap()
+            .starts_with("Failed:"));
+    }
+
+    #[test]
+    fn test_unix_view() {
+        let (mut app, backend) = fake_app();
+        app.next();
+        // Unix sockets are only scanned for once they are shown
+        assert!(app.unix_sockets.is_empty());
+        app.toggle_view();
+        assert_eq!(app.view, View::Unix);
+        assert!(app.rows.is_empty());
+        app.wait_for_scan();
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.table_state.selected(), Some(0));
+        assert!(app.get_selected_port().is_none());
+        assert_eq!(app.title(), " Listening Unix Sockets (3) ");
+
+        // The popup acts on the socket's owner, like in the TCP view
+        app.next();
+        app.open_terminate_popup();
+        app.popup_next();
+        app.popup_next();
+        assert_eq!(app.execute_popup_action(), Some((2088, true)));
+        app.kill_process(2088, true);
+        app.wait_for_scan();
+        assert_eq!(backend.kills(), vec![(2088, true)]);
+        assert_eq!(app.rows.len(), 2);
+        assert_eq!(app.get_selected_unix().unwrap().process_name, "Xorg");
+
+        app.toggle_view();
+        assert_eq!(app.view, View::Tcp);
+        assert_eq!(app.rows.len(), 5);
+
+        let mut snapshot = App::from_snapshot(Snapshot::new(vec![]));
+        snapshot.toggle_view();
+        assert_eq!(snapshot.view, View::Tcp);
+    }
+
+    #[test]
+    fn test_unix_view_during_scan() {
+        let (mut app, backend) = fake_app();
+        let resume = backend.pause_scans();
+        app.refresh();
+        // The TCP scan still running is dropped for one that lists Unix sockets
+        app.toggle_view();
+        drop(resume);
+        app.wait_for_scan();
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.status_message.as_deref(), Some("Refreshed - 5 ports"));
+    }
+
+    #[test]
+    fn test_namespace_filter() {
+        let (mut app, backend) = fake_app();
+        app.cycle_namespace();
+        assert_eq!(app.namespace_filter, NamespaceFilter::All);
+        assert!(!app.namespace_column);
+
+        let mut ports = crate::backend::fixture_ports();
+        let container = Namespace {
+            inode: 4026532301,
+            name: "docker:4f1c2a9e8b7d".to_string(),
+        };
+        ports[3].namespace = Some(container.clone());
+        backend.set_report(ScanReport {
+            ports,
+            ..Default::default()
+        });
+        app.refresh();
+        app.wait_for_scan();
+        assert!(app.namespace_column);
+        assert_eq!(app.rows[3].cells[0], "docker:4f1c2a9e8b7d");
+
+        app.cycle_namespace();
+        assert_eq!(app.namespace_filter, NamespaceFilter::Host);
+        assert_eq!(app.rows.len(), 4);
+        app.cycle_namespace();
+        assert_eq!(app.namespace_filter, NamespaceFilter::Only(container));
+        assert_eq!(app.rows.len(), 1);
+        assert_eq!(app.get_selected_port().unwrap().port, 8000);
+        assert_eq!(
+            app.title(),
+            " Listening TCP Ports (1) [netns docker:4f1c2a9e8b7d] "
+        );
+
+        // The filter survives a refresh
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.rows.len(), 1);
+        app.cycle_namespace();
+        assert_eq!(app.namespace_filter, NamespaceFilter::All);
+        assert_eq!(app.rows.len(), 5);
+    }
+
+    #[test]
+    fn test_container_actions() {
+        let (mut app, backend) = fake_app();
+        let mut ports = crate::backend::fixture_ports();
+        ports[3].process_name = "docker-proxy".to_string();
+        ports[3].cmd_args = vec!["/usr/bin/docker-proxy".to_string()];
+        backend.set_report(ScanReport {
+            ports,
+            ..Default::default()
+        });
+        backend.fail_containers(Some("permission denied"));
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.rows[3].cells[4], "docker-proxy");
+        assert_eq!(
+            app.warning_summary(),
+            vec!["containers not listed: permission denied"]
+        );
+
+        backend.fail_containers(None);
+        backend.set_containers(vec![crate::backend::fixture_container()]);
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.rows[3].cells[4], "shop-web-1 (docker-proxy)");
+        assert!(app.warning_summary().is_empty());
+
+        // Rows of other processes offer no container actions
+        assert_eq!(app.popup_buttons().len(), 3);
+        app.container_action(ContainerAction::Stop);
+        assert!(backend.container_actions().is_empty());
+
+        for _ in 0..3 {
+            app.next();
+        }
+        app.open_terminate_popup();
+        assert_eq!(app.popup_buttons().len(), 5);
+        app.popup_prev();
+        assert_eq!(app.popup_selection, PopupButton::RestartContainer);
+        assert_eq!(app.execute_popup_action(), None);
+        app.wait_for_scan();
+        let id = "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a".to_string();
+        assert_eq!(
+            backend.container_actions(),
+            vec![(id.clone(), ContainerAction::Restart)]
+        );
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Restarted container shop-web-1")
+        );
+
+        app.container_action(ContainerAction::Stop);
+        app.wait_for_scan();
+        assert_eq!(backend.container_actions()[1], (id, ContainerAction::Stop));
+        assert_eq!(app.rows[3].cells[4], "docker-proxy");
+        assert!(app.get_selected_container().is_none());
+    }
+
+    #[test]
+    fn test_host_network_container() {
+        let (mut app, backend) = fake_app();
+        let pid = app.ports[1].pid;
+        backend.set_containers(vec![crate::backend::fixture_container()]);
+        backend.set_container_pid(pid, "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a");
+        app.refresh();
+        app.wait_for_scan();
+        let name = &app.ports[1].process_name;
+        assert_eq!(app.rows[1].cells[4], format!("shop-web-1 ({name})"));
+        app.next();
+        assert_eq!(app.get_selected_container().unwrap().name, "shop-web-1");
+    }
+
+    #[test]
+    fn test_unit_actions() {
+        let backend = FakeBackend::from_fixture();
+        let postgres = Unit {
+            name: "postgresql.service".to_string(),
+            scope: crate::systemd::Scope::System,
+            activates: vec![],
+        };
+        backend.set_unit(1290, postgres.clone());
+        let mut app = App::with_backend(Box::new(backend.clone()));
+        app.wait_for_scan();
+        assert!(app.unit_column);
+        assert_eq!(app.rows[2].cells[5], "postgresql.service");
+        assert_eq!(app.rows[0].cells[5], "-");
+
+        app.next();
+        app.next();
+        app.open_terminate_popup();
+        assert_eq!(app.popup_buttons().len(), 6);
+        app.popup_prev();
+        assert_eq!(app.popup_selection, PopupButton::UnitStatus);
+        app.execute_popup_action();
+        assert_eq!(app.pending_unit_status, Some(postgres));
+
+        app.unit_action(UnitAction::Stop);
+        app.wait_for_scan();
+        assert_eq!(
+            backend.unit_actions(),
+            vec![("postgresql.service".to_string(), UnitAction::Stop)]
+        );
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Stopped postgresql.service")
+        );
+        assert_eq!(app.rows.len(), 4);
+        assert!(!app.unit_column);
+    }
+
+    #[test]
+    fn test_mine_only() {
+        let (mut app, _) = fake_app();
+        app.uid = 1000;
+        app.rows_changed();
+        let owners: Vec<Owner> = app.rows.iter().map(|r| r.owner).collect();
+        assert_eq!(
+            owners,
+            [
+                Owner::Root,
+                Owner::Mine,
+                Owner::Other,
+                Owner::Mine,
+                Owner::Mine
+            ]
+        );
+        assert_eq!(app.rows[0].cells[3], "root");
+
+        app.toggle_mine();
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.title(), " Listening TCP Ports (3) [mine] ");
+        app.next();
+        assert_eq!(app.get_selected_port().unwrap().port, 8000);
+
+        // The filter applies to the Unix view too
+        app.toggle_view();
+        app.wait_for_scan();
+        assert_eq!(app.rows.len(), 1);
+        assert_eq!(app.get_selected_unix().unwrap().process_name, "ssh-agent");
+
+        app.toggle_mine();
+        assert_eq!(app.rows.len(), 3);
+    }
+
+    #[test]
+    fn test_process_tree() {
+        let (mut app, backend) = fake_app();
+        let entry = |pid: u32, name: &str| ProcEntry {
+            pid,
+            name: name.to_string(),
+            cmd: name.to_string(),
+        };
+        backend.set_tree(ProcessTree {
+            ancestors: vec![entry(1, "systemd"), entry(4100, "bash")],
+            process: entry(812, "sshd"),
+            children: vec![entry(4300, "sshd-session")],
+            more_children: 0,
+        });
+
+        app.open_terminate_popup();
+        assert_eq!(app.tree_cursor, 2);
+        assert_eq!(app.tree_selection().unwrap().pid, 812);
+        app.tree_next();
+        app.tree_next();
+        assert_eq!(app.tree_selection().unwrap().pid, 4300);
+        for _ in 0..5 {
+            app.tree_prev();
+        }
+        assert_eq!(app.tree_selection().unwrap().pid, 1);
+        app.close_popup();
+
+        // Processes without a tree, such as ones that exited, have none
+        app.next();
+        app.open_terminate_popup();
+        assert!(app.tree.is_none());
+        app.tree_next();
+        assert!(app.tree_selection().is_none());
+    }
+
+    #[test]
+    fn test_grouped_by_process() {
+        let (mut app, backend) = fake_app();
+        app.cycle_grouping();
+        // node's two ports share a row
+        assert_eq!(app.rows.len(), 4);
+        assert_eq!(app.title(), " Listening TCP Ports (5) [by process] ");
+        assert_eq!(app.rows[1].cells[0], "\u{25b8} 2 ports");
+        assert_eq!(app.rows[1].cells[1], "(several)");
+        assert_eq!(app.rows[1].cells[4], "next dev (node)");
+        assert_eq!(app.rows[0].cells[0], "\u{25b8} 22");
+
+        app.next();
+        assert_eq!(app.get_selected_group(), Some(&[1, 4][..]));
+        app.toggle_group();
+        assert_eq!(app.rows.len(), 6);
+        assert_eq!(app.rows[2].cells[0], "  \u{2514} 3000");
+        assert_eq!(app.rows[3].cells[0], "  \u{2514} 9229");
+
+        // Collapsing from a port row returns to its process
+        app.next();
+        app.next();
+        assert_eq!(app.get_selected_port().unwrap().port, 9229);
+        app.toggle_group();
+        assert_eq!(app.rows.len(), 4);
+        assert_eq!(app.table_state.selected(), Some(1));
+
+        // Actions on a process row act on the process
+        app.open_terminate_popup();
+        app.popup_next();
+        assert_eq!(app.execute_popup_action(), Some((4242, false)));
+        app.kill_process(4242, false);
+        app.wait_for_scan();
+        assert_eq!(backend.kills(), vec![(4242, false)]);
+        assert_eq!(app.rows.len(), 3);
+
+        // Without projects the next grouping is none
+        app.cycle_grouping();
+        assert_eq!(app.rows.len(), 3);
+        assert!(app.get_selected_group().is_none());
+    }
+
+    #[test]
+    fn test_grouped_by_project() {
+        let (mut app, backend) = fake_app();
+        let shop = Project {
+            name: "shop".to_string(),
+            root: PathBuf::from("/home/dev/projects"),
+            branch: Some("main".to_string()),
+        };
+        backend.set_project(4242, shop.clone());
+        backend.set_project(5120, shop);
+        app.refresh();
+        app.wait_for_scan();
+        assert!(app.project_column);
+        assert_eq!(app.rows[1].cells[5..7], ["shop", "main"]);
+
+        app.cycle_grouping();
+        app.cycle_grouping();
+        assert_eq!(app.title(), " Listening TCP Ports (5) [by project] ");
+        assert_eq!(app.rows.len(), 2);
+        let cells = &app.rows[0].cells;
+        assert_eq!(
+            cells[..5],
+            [
+                "\u{25b8} 3 ports",
+                "(several)",
+                "2 pids",
+                "dev",
+                "node, python3"
+            ]
+        );
+        // Ports outside any project are grouped last
+        assert_eq!(app.rows[1].cells[0], "\u{25b8} 2 ports");
+        assert_eq!(app.rows[1].cells[5], "-");
+
+        app.toggle_group();
+        assert_eq!(app.rows.len(), 5);
+        app.next();
+        assert!(app.get_selected_project_group().is_none());
+        app.previous();
+
+        // Terminating a project row stops every process of the project
+        app.open_terminate_popup();
+        assert_eq!(app.popup_buttons().len(), 3);
+        app.popup_next();
+        assert_eq!(app.execute_popup_action(), None);
+        app.wait_for_scan();
+        assert_eq!(backend.kills(), vec![(4242, false), (5120, false)]);
+        assert_eq!(app.rows.len(), 1);
+
+        app.cycle_grouping();
+        assert_eq!(app.grouping, Grouping::None);
+    }
+
+    #[test]
+    fn test_kill_project_denied() {
+        let (mut app, backend) = fake_app();
+        let shop = Project {
+            name: "shop".to_string(),
+            root: PathBuf::from("/home/dev/projects"),
+            branch: None,
+        };
+        backend.set_project(4242, shop.clone());
+        backend.set_project(5120, shop);
+        app.refresh();
+        app.wait_for_scan();
+        app.cycle_grouping();
+        app.cycle_grouping();
+
+        backend.fail_kills(Some(io::ErrorKind::PermissionDenied));
+        app.kill_project(true);
+        if app.can_elevate() {
+            // Both are retried as root after a single confirmation
+            assert_eq!(
+                app.elevation_prompt,
+                Some(Elevation::Kill {
+                    pids: vec![4242, 5120],
+                    force: true
+                })
+            );
+        } else {
+            assert_eq!(app.elevation_prompt, None);
+            let status = app.status_message.clone().unwrap_or_default();
+            assert!(
+                status.starts_with("Force killed 0 of 2 processes of shop - PID 4242"),
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_orphans() {
+        let (mut app, backend) = fake_app();
+        app.toggle_orphans();
+        assert!(!app.orphans_only);
+
+        let web = Project {
+            name: "web".to_string(),
+            root: PathBuf::from("/home/dev/projects/web"),
+            branch: None,
+        };
+        backend.set_project(4242, web);
+        backend.set_detached(4242);
+        // Detached, but not from a project
+        backend.set_detached(1290);
+        app.refresh();
+        app.wait_for_scan();
+        assert!(app.flags_column);
+        assert_eq!(app.rows[1].cells[7], "orphan");
+        assert_eq!(app.rows[2].cells[7], "");
+
+        app.toggle_orphans();
+        assert_eq!(app.title(), " Listening TCP Ports (2) [orphans] ");
+        assert_eq!(app.rows.len(), 2);
+
+        app.offer_cleanup();
+        assert_eq!(
+            app.cleanup_prompt,
+            Some(vec![(4242, "next dev (node) in web".to_string())])
+        );
+
+        // A denied kill is offered as root, or reported when that is not
+        // possible
+        backend.fail_kills(Some(io::ErrorKind::PermissionDenied));
+        app.confirm_cleanup();
+        if app.can_elevate() {
+            assert_eq!(
+                app.elevation_prompt.take(),
+                Some(Elevation::Kill {
+                    pids: vec![4242],
+                    force: false
+                })
+            );
+        } else {
+            assert_eq!(
+                app.status_message.as_deref(),
+                Some("Terminated 0 of 1 orphaned dev server - PID 4242: permission denied")
+            );
+        }
+        app.wait_for_scan();
+        backend.fail_kills(None);
+
+        app.offer_cleanup();
+        app.confirm_cleanup();
+        app.wait_for_scan();
+        assert_eq!(backend.kills(), vec![(4242, false)]);
+        assert!(app.rows.is_empty());
+        assert!(!app.flags_column);
+    }
+
+    #[test]
+    fn test_stale_binary() {
+        let (mut app, backend) = fake_app();
+        backend.set_stale(5120);
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.rows[3].cells[5], "stale binary");
+        assert_eq!(app.rows[1].cells[5], "");
+
+        app.next();
+        app.open_terminate_popup();
+        assert_eq!(app.popup_buttons().len(), 3);
+        app.close_popup();
+        app.restart_process();
+        assert!(backend.restarts().is_empty());
+
+        app.next();
+        app.next();
+        app.open_terminate_popup();
+        assert_eq!(
+            app.popup_buttons().last(),
+            Some(&PopupButton::RestartProcess)
+        );
+        app.popup_prev();
+        assert_eq!(app.execute_popup_action(), None);
+        // The restart runs in the background and rescans when done
+        assert!(app.restarting().is_some());
+        app.wait_for_scan();
+        assert!(app.restarting().is_none());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Restarted python3 (PID 5120 -> 5121)")
+        );
+        assert_eq!(backend.restarts(), vec![5120]);
+        assert_eq!(app.ports[3].pid, 5121);
+        assert!(!app.flags_column);
+    }
+
+    #[test]
+    fn test_scan_failure_keeps_rows() {
+        let (mut app, backend) = fake_app();
+        backend.fail_scans(Some("netlink went away"));
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.ports.len(), 5);
+        assert!(app.error.as_deref().unwrap().contains("netlink went away"));
+        assert_eq!(app.status_message.as_deref(), Some("Refresh failed"));
+
+        backend.fail_scans(None);
+        app.refresh();
+        app.wait_for_scan();
+        assert_eq!(app.error, None);
+    }
+
+    #[test]
+    fn test_cancel_scan() {
+        let (mut app, backend) = fake_app();
+        backend.set_report(ScanReport::default());
+        app.refresh();
+        assert!(app.is_scanning());
+        assert!(app.spinner().is_some());
+
+        app.cancel_scan();
+        app.poll_scan();
+        assert!(!app.is_scanning());
+        assert_eq!(app.ports.len(), 5);
+        assert_eq!(app.status_message.as_deref(), Some("Scan cancelled"));
+    }
+
+    #[test]
+    fn test_refresh_waits_for_cancelled_scan() {
+        let (mut app, backend) = fake_app();
+        let resume = backend.pause_scans();
+        app.refresh();
+        app.cancel_scan();
+        backend.set_report(ScanReport::default());
+
+        // The cancelled worker still holds the backend
+        app.refresh();
+        app.poll_scan();
+        assert!(!app.is_scanning());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Waiting for the cancelled scan to stop")
+        );
+
+        // Once it stops, the queued scan runs
+        resume.send(()).unwrap();
+        resume.send(()).unwrap();
+        app.wait_for_scan();
+        assert_eq!(backend.scans(), 3);
+        assert!(app.ports.is_empty());
+        assert_eq!(app.status_message.as_deref(), Some("Refreshed - 0 ports"));
+    }
+
+    #[test]
+    fn test_remote_kill_goes_through_agent() {
+        use crate::agent::{spawn_agent, spawn_child, wait_for_exit};
+        use std::time::Duration;
+
+        let mut child = spawn_child(true);
+        let client = AgentClient::new(spawn_agent("secret"), "secret".to_string());
+        let mut app = App::from_agents(vec![client]);
+        let local = FakeBackend::from_fixture();
+        app.backend = Arc::new(local.clone());
+        app.wait_for_scan();
+
+        let pid = child.id();
+        let row = app.shown.iter().position(|&i| app.ports[i].pid == pid);
+        app.table_state.select(row);
+        app.kill_process(pid, false);
+        let status = wait_for_exit(&mut child, Duration::from_secs(5));
+        if status.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        assert!(row.is_some(), "the agent did not report the child");
+        assert!(status.is_some(), "the child is still running");
+        assert!(local.kills().is_empty());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some(format!("Terminated PID {pid}").as_str())
+        );
+    }
+
+    #[test]
+    fn test_remote_scan_in_background() {
+        let addr = crate::agent::AgentAddr::Unix(PathBuf::from("/nonexistent/agent.sock"));
+        let mut app = App::from_agents(vec![AgentClient::new(addr, "secret".to_string())]);
+        assert!(app.is_scanning());
+        app.wait_for_scan();
+        let error = app.error.clone().unwrap_or_default();
+        assert!(error.starts_with("unix:/nonexistent/agent.sock"), "{error}");
+
+        app.refresh();
+        assert!(app.is_scanning());
+        app.wait_for_scan();
+        assert_eq!(app.status_message.as_deref(), Some("Refreshed - 0 ports"));
+    }
+}
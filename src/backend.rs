@@ -0,0 +1,433 @@
+use crate::docker::{self, Container, ContainerAction, DockerClient};
+use crate::orphan;
+use crate::proctree::{self, ProcessTree};
+use crate::project::{self, Project};
+use crate::stale;
+use crate::systemd::{self, Unit, UnitAction};
+use port_checker::ports::{PortInfo, ScanError, ScanOptions, ScanReport, Scanner};
+use port_checker::signal;
+use std::io;
+use std::sync::Mutex;
+
+/// Where the live view gets its listeners from and sends its signals to.
+/// Scans run on a worker thread while the UI keeps using the backend.
+pub trait ScanBackend: Send + Sync {
+    /// Listeners, plus listening Unix sockets when `unix` is set
+    fn scan(&self, unix: bool) -> Result<ScanReport, ScanError>;
+
+    fn kill(&self, pid: u32, force: bool) -> io::Result<()>;
+
+    /// Running containers, to name the ones behind listeners
+    fn containers(&self) -> io::Result<Vec<Container>>;
+
+    fn container_action(&self, id: &str, action: ContainerAction) -> io::Result<()>;
+
+    /// ID of the container each port's process runs in, from its cgroup,
+    /// in order
+    fn container_ids(&self, ports: &[PortInfo]) -> Vec<Option<String>>;
+
+    /// systemd unit of each port, in order
+    fn units(&self, ports: &[PortInfo]) -> Vec<Option<Unit>>;
+
+    /// Stop or restart a unit through systemctl
+    fn unit_action(&self, unit: &Unit, action: UnitAction) -> Result<(), String>;
+
+    /// Parents and children of `pid`, or `None` once it has exited
+    fn process_tree(&self, pid: u32) -> Option<ProcessTree>;
+
+    /// Project each port's process runs from, in order
+    fn projects(&self, ports: &[PortInfo]) -> Vec<Option<Project>>;
+
+    /// Whether each port's process was reparented to init or a subreaper
+    /// and has no controlling terminal, in order
+    fn detached(&self, ports: &[PortInfo]) -> Vec<bool>;
+
+    /// Whether each port's process runs a deleted or rebuilt binary, in order
+    fn stale_binaries(&self, ports: &[PortInfo]) -> Vec<bool>;
+
+    /// Stop `port`'s process and start its command again, returning the
+    /// new PID
+    fn restart(&self, port: &PortInfo) -> io::Result<u32>;
+}
+
+/// The real socket table and processes of this machine
+pub struct SystemBackend {
+    /// Keeps process details cached between refreshes
+    scanner: Mutex<Scanner>,
+    options: ScanOptions,
+    docker: DockerClient,
+}
+
+impl SystemBackend {
+    pub fn new(options: ScanOptions) -> Self {
+        Self {
+            scanner: Mutex::default(),
+            options,
+            docker: DockerClient::from_env(),
+        }
+    }
+}
+
+impl Default for SystemBackend {
+    fn default() -> Self {
+        Self::new(ScanOptions::default())
+    }
+}
+
+impl ScanBackend for SystemBackend {
+    fn scan(&self, unix: bool) -> Result<ScanReport, ScanError> {
+        let mut options = self.options.clone();
+        options.unix |= unix;
+        let mut scanner = self.scanner.lock().unwrap_or_else(|e| e.into_inner());
+        scanner.scan(&options)
+    }
+
+    fn kill(&self, pid: u32, force: bool) -> io::Result<()> {
+        signal::send_signal(pid, force)
+    }
+
+    fn containers(&self) -> io::Result<Vec<Container>> {
+        self.docker.containers()
+    }
+
+    fn container_action(&self, id: &str, action: ContainerAction) -> io::Result<()> {
+        self.docker.act(id, action)
+    }
+
+    fn container_ids(&self, ports: &[PortInfo]) -> Vec<Option<String>> {
+        docker::cgroup_ids(ports)
+    }
+
+    fn units(&self, ports: &[PortInfo]) -> Vec<Option<Unit>> {
+        systemd::units(ports)
+    }
+
+    fn unit_action(&self, unit: &Unit, action: UnitAction) -> Result<(), String> {
+        systemd::run(unit, action)
+    }
+
+    fn process_tree(&self, pid: u32) -> Option<ProcessTree> {
+        proctree::load(pid)
+    }
+
+    fn projects(&self, ports: &[PortInfo]) -> Vec<Option<Project>> {
+        project::projects(ports)
+    }
+
+    fn detached(&self, ports: &[PortInfo]) -> Vec<bool> {
+        orphan::detached(ports)
+    }
+
+    fn stale_binaries(&self, ports: &[PortInfo]) -> Vec<bool> {
+        stale::stale_binaries(ports)
+    }
+
+    fn restart(&self, port: &PortInfo) -> io::Result<u32> {
+        stale::restart(port)
+    }
+}
+
+#[cfg(test)]
+pub use fake::{fixture_container, fixture_ports, fixture_report, FakeBackend};
+
+#[cfg(test)]
+mod fake {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    /// Listener list fed from fixtures, for driving the app in tests.
+    ///
+    /// Clones share state, so a test can keep one handle to inspect and
+    /// change what the app's copy sees. Killing a PID removes its TCP and
+    /// Unix listeners from later scans, and stopping a container removes it.
+    #[derive(Clone, Default)]
+    pub struct FakeBackend(Arc<Mutex<State>>);
+
+    #[derive(Default)]
+    struct State {
+        report: ScanReport,
+        containers: Vec<Container>,
+        container_pids: Vec<(u32, String)>,
+        container_actions: Vec<(String, ContainerAction)>,
+        units: Vec<(u32, Unit)>,
+        unit_actions: Vec<(String, UnitAction)>,
+        trees: Vec<ProcessTree>,
+        projects: Vec<(u32, Project)>,
+        detached: Vec<u32>,
+        stale: Vec<u32>,
+        restarts: Vec<u32>,
+        scan_error: Option<String>,
+        container_error: Option<String>,
+        kill_error: Option<io::ErrorKind>,
+        kills: Vec<(u32, bool)>,
+        scans: usize,
+        /// Scans wait for a message on this before reading the report
+        resume: Option<Receiver<()>>,
+    }
+
+    /// The listeners in `tests/fixtures/ports.json`
+    pub fn fixture_report() -> ScanReport {
+        serde_json::from_str(include_str!("../tests/fixtures/ports.json")).expect("valid fixture")
+    }
+
+    /// The TCP ports in `tests/fixtures/ports.json`
+    pub fn fixture_ports() -> Vec<PortInfo> {
+        fixture_report().ports
+    }
+
+    /// A compose container publishing port 8000 on every address
+    pub fn fixture_container() -> Container {
+        Container {
+            id: "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a".to_string(),
+            name: "shop-web-1".to_string(),
+            image: "nginx:1.27".to_string(),
+            project: Some("shop".to_string()),
+            published: vec![(None, 8000)],
+        }
+    }
+
+    impl FakeBackend {
+        pub fn new(ports: Vec<PortInfo>) -> Self {
+            let backend = Self::default();
+            backend.state().report.ports = ports;
+            backend
+        }
+
+        /// Backend serving `tests/fixtures/ports.json`
+        pub fn from_fixture() -> Self {
+            let backend = Self::default();
+            backend.set_report(fixture_report());
+            backend
+        }
+
+        fn state(&self) -> MutexGuard<'_, State> {
+            self.0.lock().unwrap()
+        }
+
+        pub fn set_report(&self, report: ScanReport) {
+            self.state().report = report;
+        }
+
+        pub fn set_containers(&self, containers: Vec<Container>) {
+            self.state().containers = containers;
+        }
+
+        /// Put `pid` in the cgroup of container `id`
+        pub fn set_container_pid(&self, pid: u32, id: &str) {
+            self.state().container_pids.push((pid, id.to_string()));
+        }
+
+        /// Container actions taken so far, as (id, action)
+        pub fn container_actions(&self) -> Vec<(String, ContainerAction)> {
+            self.state().container_actions.clone()
+        }
+
+        /// Put `pid`'s listeners in `unit`
+        pub fn set_unit(&self, pid: u32, unit: Unit) {
+            self.state().units.push((pid, unit));
+        }
+
+        /// Unit actions taken so far, as (unit name, action)
+        pub fn unit_actions(&self) -> Vec<(String, UnitAction)> {
+            self.state().unit_actions.clone()
+        }
+
+        /// Serve `tree` for its process
+        pub fn set_tree(&self, tree: ProcessTree) {
+            self.state().trees.push(tree);
+        }
+
+        /// Run `pid`'s listeners from `project`
+        pub fn set_project(&self, pid: u32, project: Project) {
+            self.state().projects.push((pid, project));
+        }
+
+        /// Make `pid` look like it lost its parent and terminal
+        pub fn set_detached(&self, pid: u32) {
+            self.state().detached.push(pid);
+        }
+
+        /// Make `pid` look like it runs a deleted binary until restarted
+        pub fn set_stale(&self, pid: u32) {
+            self.state().stale.push(pid);
+        }
+
+        /// PIDs restarted so far
+        pub fn restarts(&self) -> Vec<u32> {
+            self.state().restarts.clone()
+        }
+
+        /// Make scans fail with `message` until cleared with `None`
+        pub fn fail_scans(&self, message: Option<&str>) {
+            self.state().scan_error = message.map(str::to_string);
+        }
+
+        /// Make listing containers fail with `message` until cleared with
+        /// `None`
+        pub fn fail_containers(&self, message: Option<&str>) {
+            self.state().container_error = message.map(str::to_string);
+        }
+
+        /// Make kills fail with `kind` until cleared with `None`
+        pub fn fail_kills(&self, kind: Option<io::ErrorKind>) {
+            self.state().kill_error = kind;
+        }
+
+        /// Signals sent so far, as (pid, force)
+        pub fn kills(&self) -> Vec<(u32, bool)> {
+            self.state().kills.clone()
+        }
+
+        pub fn scans(&self) -> usize {
+            self.state().scans
+        }
+
+        /// Make each scan wait for a message on the returned channel, to
+        /// keep it running while the test acts
+        pub fn pause_scans(&self) -> Sender<()> {
+            let (tx, rx) = mpsc::channel();
+            self.state().resume = Some(rx);
+            tx
+        }
+    }
+
+    impl ScanBackend for FakeBackend {
+        fn scan(&self, unix: bool) -> Result<ScanReport, ScanError> {
+            let resume = self.state().resume.take();
+            if let Some(resume) = resume {
+                let _ = resume.recv();
+                self.state().resume = Some(resume);
+            }
+            let mut state = self.state();
+            state.scans += 1;
+            match &state.scan_error {
+                Some(message) => Err(ScanError::SocketTable(message.clone())),
+                None if unix => Ok(state.report.clone()),
+                None => Ok(ScanReport {
+                    unix_sockets: vec![],
+                    ..state.report.clone()
+                }),
+            }
+        }
+
+        fn kill(&self, pid: u32, force: bool) -> io::Result<()> {
+            let mut state = self.state();
+            if let Some(kind) = state.kill_error {
+                return Err(io::Error::from(kind));
+            }
+            let report = &mut state.report;
+            if !report.ports.iter().any(|p| p.pid == pid)
+                && !report.unix_sockets.iter().any(|u| u.pid == pid)
+            {
+                return Err(io::Error::from_raw_os_error(nix::libc::ESRCH));
+            }
+            report.ports.retain(|p| p.pid != pid);
+            report.unix_sockets.retain(|u| u.pid != pid);
+            state.kills.push((pid, force));
+            Ok(())
+        }
+
+        fn containers(&self) -> io::Result<Vec<Container>> {
+            let state = self.state();
+            match &state.container_error {
+                Some(message) => Err(io::Error::other(message.clone())),
+                None => Ok(state.containers.clone()),
+            }
+        }
+
+        fn container_action(&self, id: &str, action: ContainerAction) -> io::Result<()> {
+            let mut state = self.state();
+            if !state.containers.iter().any(|c| c.id == id) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No such container: {id}"),
+                ));
+            }
+            if action == ContainerAction::Stop {
+                state.containers.retain(|c| c.id != id);
+            }
+            state.container_actions.push((id.to_string(), action));
+            Ok(())
+        }
+
+        fn container_ids(&self, ports: &[PortInfo]) -> Vec<Option<String>> {
+            let state = self.state();
+            ports
+                .iter()
+                .map(|p| {
+                    let found = state.container_pids.iter().find(|(pid, _)| *pid == p.pid);
+                    found.map(|(_, id)| id.clone())
+                })
+                .collect()
+        }
+
+        fn units(&self, ports: &[PortInfo]) -> Vec<Option<Unit>> {
+            let state = self.state();
+            ports
+                .iter()
+                .map(|p| {
+                    let unit = state.units.iter().find(|(pid, _)| *pid == p.pid);
+                    unit.map(|(_, unit)| unit.clone())
+                })
+                .collect()
+        }
+
+        fn unit_action(&self, unit: &Unit, action: UnitAction) -> Result<(), String> {
+            let mut state = self.state();
+            // Stopping a unit ends its processes and their listeners
+            if action == UnitAction::Stop {
+                let pids: Vec<u32> = state
+                    .units
+                    .iter()
+                    .filter(|(_, u)| u.name == unit.name)
+                    .map(|(pid, _)| *pid)
+                    .collect();
+                state.report.ports.retain(|p| !pids.contains(&p.pid));
+            }
+            state.unit_actions.push((unit.name.clone(), action));
+            Ok(())
+        }
+
+        fn process_tree(&self, pid: u32) -> Option<ProcessTree> {
+            let state = self.state();
+            state.trees.iter().find(|t| t.process.pid == pid).cloned()
+        }
+
+        fn projects(&self, ports: &[PortInfo]) -> Vec<Option<Project>> {
+            let state = self.state();
+            ports
+                .iter()
+                .map(|p| {
+                    let project = state.projects.iter().find(|(pid, _)| *pid == p.pid);
+                    project.map(|(_, project)| project.clone())
+                })
+                .collect()
+        }
+
+        fn detached(&self, ports: &[PortInfo]) -> Vec<bool> {
+            let state = self.state();
+            ports
+                .iter()
+                .map(|p| state.detached.contains(&p.pid))
+                .collect()
+        }
+
+        fn stale_binaries(&self, ports: &[PortInfo]) -> Vec<bool> {
+            let state = self.state();
+            ports.iter().map(|p| state.stale.contains(&p.pid)).collect()
+        }
+
+        /// The restarted process gets the next free PID and a current binary
+        fn restart(&self, port: &PortInfo) -> io::Result<u32> {
+            let mut state = self.state();
+            let pid = state.report.ports.iter().map(|p| p.pid).max().unwrap_or(0) + 1;
+            for p in state.report.ports.iter_mut().filter(|p| p.pid == port.pid) {
+                p.pid = pid;
+            }
+            state.stale.retain(|&stale| stale != port.pid);
+            state.restarts.push(port.pid);
+            Ok(pid)
+        }
+    }
+}
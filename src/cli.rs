@@ -0,0 +1,334 @@
+use crate::agent::{self, AgentAddr};
+use crate::{history, metrics};
+use color_eyre::eyre::{bail, Result};
+use port_checker::ports::{Collector, ScanOptions};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage:
+  port-checker [--collector NAME] [--namespaces] [COMMAND]
+
+Commands:
+  port-checker                     Browse listening ports in the TUI
+  port-checker save <FILE>         Save the current listening ports to FILE
+  port-checker load <FILE>         Browse a saved snapshot (read-only)
+  port-checker diff [--tui] <OLD> [NEW]
+                                   Compare snapshot OLD against NEW, or against
+                                   the live state when NEW is omitted
+  port-checker daemon [--interval SECS] [--retention-days DAYS] [--history FILE]
+                                   Record listener open/close events in the
+                                   background (default: every 10s, keep 7 days)
+  port-checker timeline [--history FILE]
+                                   Browse recorded history in the TUI
+  port-checker serve-metrics [--listen ADDR] [--expect PORT,...]
+                                   Serve Prometheus metrics on ADDR/metrics
+                                   (default: 127.0.0.1:9464)
+  port-checker agent --listen ADDR [--token-file FILE]
+                                   Serve scans and kill requests to remote
+                                   TUIs on tcp://HOST:PORT or unix:PATH
+  port-checker connect [--token-file FILE] ADDR...
+                                   Browse the ports of one or more agents
+  port-checker help                Show this help
+
+Options for every command that scans:
+  --collector NAME                 How to read the socket table: netstat2
+                                   (default) or sock-diag, which queries Linux
+                                   NETLINK_SOCK_DIAG. Defaults to the
+                                   PORT_CHECKER_COLLECTOR environment variable.
+  --namespaces                     Also list listeners in other network
+                                   namespaces, such as containers' and
+                                   `ip netns` ones (Linux). Every scan then
+                                   reads one socket table per namespace.
+
+Agents and clients share a token read from --token-file, or from the
+PORT_CHECKER_TOKEN environment variable.";
+
+/// Environment variable selecting how the socket table is read when
+/// `--collector` is not given
+pub const COLLECTOR_ENV: &str = "PORT_CHECKER_COLLECTOR";
+
+/// What to run and the options of every scan it makes
+pub struct Cli {
+    pub command: Command,
+    pub options: ScanOptions,
+}
+
+pub enum Command {
+    Tui,
+    Save {
+        path: PathBuf,
+    },
+    Load {
+        path: PathBuf,
+    },
+    Diff {
+        old: PathBuf,
+        new: Option<PathBuf>,
+        tui: bool,
+    },
+    Daemon {
+        history: PathBuf,
+        interval_secs: u64,
+        retention_days: u64,
+    },
+    Timeline {
+        history: PathBuf,
+    },
+    ServeMetrics {
+        listen: SocketAddr,
+        expected: Vec<u16>,
+    },
+    Agent {
+        listen: AgentAddr,
+        token: String,
+    },
+    Connect {
+        agents: Vec<AgentAddr>,
+        token: String,
+    },
+    /// Internal: run under sudo/pkexec by the TUI, printing JSON results
+    Helper {
+        args: Vec<String>,
+    },
+    Help,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli> {
+    let mut options = ScanOptions::default();
+    let mut collector = None;
+    // Scan options are accepted before or after the command
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--collector" => {
+                let name = flag_value(&arg, args.next().as_ref())?.to_string();
+                collector = Some(("--collector", name));
+            }
+            "--namespaces" => options.namespaces = true,
+            _ => rest.push(arg),
+        }
+    }
+    let collector = collector.or_else(|| Some((COLLECTOR_ENV, std::env::var(COLLECTOR_ENV).ok()?)));
+    if let Some((source, name)) = collector {
+        match name.parse::<Collector>() {
+            Ok(collector) => options.collector = collector,
+            Err(e) => bail!("{source}: {e}"),
+        }
+    }
+    Ok(Cli {
+        command: parse_command(rest)?,
+        options,
+    })
+}
+
+fn parse_command(args: Vec<String>) -> Result<Command> {
+    let mut args = args.into_iter();
+    let Some(command) = args.next() else {
+        return Ok(Command::Tui);
+    };
+
+    let rest: Vec<String> = args.collect();
+    match command.as_str() {
+        "save" => Ok(Command::Save {
+            path: single_path(&command, &rest)?,
+        }),
+        "load" => Ok(Command::Load {
+            path: single_path(&command, &rest)?,
+        }),
+        "diff" => {
+            let tui = rest.iter().any(|a| a == "--tui");
+            let paths: Vec<&String> = rest.iter().filter(|a| *a != "--tui").collect();
+            if let Some(flag) = paths.iter().find(|a| a.starts_with("--")) {
+                bail!("unknown option '{flag}' for diff\n\n{USAGE}");
+            }
+            match paths.as_slice() {
+                [old] => Ok(Command::Diff {
+                    old: PathBuf::from(old),
+                    new: None,
+                    tui,
+                }),
+                [old, new] => Ok(Command::Diff {
+                    old: PathBuf::from(old),
+                    new: Some(PathBuf::from(new)),
+                    tui,
+                }),
+                _ => bail!("diff expects one or two snapshot files\n\n{USAGE}"),
+            }
+        }
+        "daemon" => {
+            let mut history = history::default_path();
+            let mut interval_secs = history::DEFAULT_INTERVAL_SECS;
+            let mut retention_days = history::DEFAULT_RETENTION_DAYS;
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--history" => history = PathBuf::from(flag_value(arg, rest.next())?),
+                    "--interval" => interval_secs = parse_number(arg, rest.next())?.max(1),
+                    "--retention-days" => retention_days = parse_number(arg, rest.next())?,
+                    other => bail!("unknown argument '{other}' for daemon\n\n{USAGE}"),
+                }
+            }
+            Ok(Command::Daemon {
+                history,
+                interval_secs,
+                retention_days,
+            })
+        }
+        "timeline" => {
+            let mut history = history::default_path();
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--history" => history = PathBuf::from(flag_value(arg, rest.next())?),
+                    other => bail!("unknown argument '{other}' for timeline\n\n{USAGE}"),
+                }
+            }
+            Ok(Command::Timeline { history })
+        }
+        "serve-metrics" => {
+            let mut listen = metrics::DEFAULT_LISTEN;
+            let mut expected = Vec::new();
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--listen" => listen = flag_value(arg, rest.next())?,
+                    "--expect" => {
+                        for port in flag_value(arg, rest.next())?.split(',') {
+                            match port.trim().parse() {
+                                Ok(port) => expected.push(port),
+                                Err(_) => bail!("--expect expects port numbers, got '{port}'"),
+                            }
+                        }
+                    }
+                    other => bail!("unknown argument '{other}' for serve-metrics\n\n{USAGE}"),
+                }
+            }
+            let listen = match listen.parse() {
+                Ok(addr) => addr,
+                Err(_) => bail!("--listen expects an address like 127.0.0.1:9464, got '{listen}'"),
+            };
+            Ok(Command::ServeMetrics { listen, expected })
+        }
+        "agent" => {
+            let mut listen = None;
+            let mut token_file = None;
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--listen" => listen = Some(AgentAddr::parse(flag_value(arg, rest.next())?)),
+                    "--token-file" => token_file = Some(flag_value(arg, rest.next())?),
+                    other => bail!("unknown argument '{other}' for agent\n\n{USAGE}"),
+                }
+            }
+            let Some(listen) = listen else {
+                bail!("agent expects --listen ADDR\n\n{USAGE}");
+            };
+            Ok(Command::Agent {
+                listen,
+                token: read_token(token_file)?,
+            })
+        }
+        "connect" => {
+            let mut agents = Vec::new();
+            let mut token_file = None;
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--token-file" => token_file = Some(flag_value(arg, rest.next())?),
+                    flag if flag.starts_with("--") => {
+                        bail!("unknown option '{flag}' for connect\n\n{USAGE}")
+                    }
+                    addr => agents.push(AgentAddr::parse(addr)),
+                }
+            }
+            if agents.is_empty() {
+                bail!("connect expects at least one agent address\n\n{USAGE}");
+            }
+            Ok(Command::Connect {
+                agents,
+                token: read_token(token_file)?,
+            })
+        }
+        "helper" => Ok(Command::Helper { args: rest }),
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        other => bail!("unknown command '{other}'\n\n{USAGE}"),
+    }
+}
+
+fn single_path(command: &str, rest: &[String]) -> Result<PathBuf> {
+    match rest {
+        [path] => Ok(PathBuf::from(path)),
+        _ => bail!("{command} expects exactly one file\n\n{USAGE}"),
+    }
+}
+
+fn flag_value<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a str> {
+    match value {
+        Some(value) => Ok(value),
+        None => bail!("{flag} expects a value\n\n{USAGE}"),
+    }
+}
+
+fn parse_number(flag: &str, value: Option<&String>) -> Result<u64> {
+    let value = flag_value(flag, value)?;
+    match value.parse() {
+        Ok(n) => Ok(n),
+        Err(_) => bail!("{flag} expects a number, got '{value}'"),
+    }
+}
+
+/// Read the shared agent token from `path`, or from the environment
+fn read_token(path: Option<&str>) -> Result<String> {
+    let token = match path {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(token) => token,
+            Err(e) => bail!("reading token file {path}: {e}"),
+        },
+        None => std::env::var(agent::TOKEN_ENV).unwrap_or_default(),
+    };
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        bail!(
+            "a shared token is required: pass --token-file FILE or set {}",
+            agent::TOKEN_ENV
+        );
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_scan_options() {
+        let cli = parse(args("--collector sock-diag save ports.json")).unwrap();
+        assert_eq!(cli.options.collector, Collector::SockDiag);
+        assert!(matches!(cli.command, Command::Save { .. }));
+
+        let cli = parse(args("daemon --interval 5 --collector netstat2")).unwrap();
+        assert_eq!(cli.options.collector, Collector::Netstat2);
+        assert!(matches!(
+            cli.command,
+            Command::Daemon {
+                interval_secs: 5,
+                ..
+            }
+        ));
+
+        assert!(!cli.options.namespaces);
+
+        let cli = parse(args("--namespaces")).unwrap();
+        assert!(cli.options.namespaces);
+        assert!(matches!(cli.command, Command::Tui));
+
+        assert!(parse(args("--collector procfs")).is_err());
+        assert!(parse(args("--collector")).is_err());
+    }
+}
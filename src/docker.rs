@@ -0,0 +1,480 @@
+//! Docker containers behind listeners, from the Docker Engine API.
+//!
+//! Published ports show up as `docker-proxy` (or the rootless port driver)
+//! on the host, and container processes as listeners in a `docker:<id>`
+//! network namespace. Processes of host-network containers, or whose proxy
+//! is not ours to see, are found through the container's cgroup in
+//! `/proc/<pid>/cgroup`. All are matched against `GET /containers/json` to
+//! name the container, and containers can be stopped or restarted.
+
+use port_checker::ports::PortInfo;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Where the Docker daemon listens unless `DOCKER_HOST` says otherwise
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// Timeout for connecting and listing containers
+const IO_TIMEOUT_SECS: u64 = 5;
+
+/// Stop and restart wait up to 10 seconds for the container to exit
+/// before killing it, so allow for that
+const ACTION_TIMEOUT_SECS: u64 = 30;
+
+/// Longest accepted response, so a bad peer cannot exhaust memory
+const MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Processes that forward published ports to a container
+const PROXIES: [&str; 3] = ["docker-proxy", "rootlesskit", "rootlessport"];
+
+/// Compose records the project a container belongs to in this label
+const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+
+/// A running container, as far as the table needs to know it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Container {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    /// Compose project, for containers started by `docker compose`
+    pub project: Option<String>,
+    /// Published TCP ports as (host address, host port); no address means
+    /// every address
+    pub published: Vec<(Option<IpAddr>, u16)>,
+}
+
+impl Container {
+    /// ID in the 12 character form `docker ps` prints
+    pub fn short_id(&self) -> &str {
+        self.id.get(..12).unwrap_or(&self.id)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerAction {
+    Stop,
+    Restart,
+}
+
+impl ContainerAction {
+    /// Past tense for status messages
+    pub fn done(self) -> &'static str {
+        match self {
+            ContainerAction::Stop => "Stopped",
+            ContainerAction::Restart => "Restarted",
+        }
+    }
+
+    fn endpoint(self) -> &'static str {
+        match self {
+            ContainerAction::Stop => "stop",
+            ContainerAction::Restart => "restart",
+        }
+    }
+}
+
+/// One entry of `GET /containers/json`
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Summary {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+    #[serde(default)]
+    ports: Vec<PortSummary>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PortSummary {
+    #[serde(rename = "IP")]
+    ip: Option<String>,
+    public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    kind: String,
+}
+
+impl From<Summary> for Container {
+    fn from(summary: Summary) -> Self {
+        let name = summary
+            .names
+            .first()
+            .map(|n| n.trim_start_matches('/').to_string())
+            .unwrap_or_else(|| summary.id.get(..12).unwrap_or(&summary.id).to_string());
+        Container {
+            project: summary
+                .labels
+                .and_then(|mut labels| labels.remove(COMPOSE_PROJECT_LABEL)),
+            published: summary
+                .ports
+                .iter()
+                .filter(|p| p.kind == "tcp")
+                .filter_map(|p| {
+                    let ip = p.ip.as_deref().and_then(|ip| ip.parse().ok());
+                    Some((ip, p.public_port?))
+                })
+                .collect(),
+            id: summary.id,
+            name,
+            image: summary.image,
+        }
+    }
+}
+
+/// Client for the Docker Engine API on a Unix socket
+#[derive(Clone, Debug)]
+pub struct DockerClient {
+    socket: PathBuf,
+}
+
+impl DockerClient {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// The daemon `DOCKER_HOST` points to when it is a `unix://` address,
+    /// otherwise the default socket
+    pub fn from_env() -> Self {
+        let socket = std::env::var("DOCKER_HOST")
+            .ok()
+            .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET));
+        Self::new(socket)
+    }
+
+    /// Running containers
+    pub fn containers(&self) -> io::Result<Vec<Container>> {
+        let body = self.request("GET", "/containers/json", IO_TIMEOUT_SECS)?;
+        let summaries: Vec<Summary> = serde_json::from_slice(&body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(summaries.into_iter().map(Container::from).collect())
+    }
+
+    /// Stop or restart container `id`. Stopping a stopped container succeeds.
+    pub fn act(&self, id: &str, action: ContainerAction) -> io::Result<()> {
+        let path = format!("/containers/{id}/{}", action.endpoint());
+        self.request("POST", &path, ACTION_TIMEOUT_SECS).map(drop)
+    }
+
+    /// Send one request and return the body of a successful response
+    fn request(&self, method: &str, path: &str, timeout_secs: u64) -> io::Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        let timeout = Some(Duration::from_secs(timeout_secs));
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        // HTTP/1.0 makes the daemon close the connection after the response
+        // instead of chunking it
+        write!(
+            stream,
+            "{method} {path} HTTP/1.0\r\nHost: docker\r\nContent-Length: 0\r\n\r\n"
+        )?;
+        let mut response = Vec::new();
+        stream.take(MAX_RESPONSE_BYTES).read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+}
+
+/// Body of an HTTP response, or the daemon's error message
+fn parse_response(response: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| invalid("truncated response from the Docker daemon"))?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+
+    let status: u16 = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("malformed response from the Docker daemon"))?;
+
+    let chunked = head.lines().skip(1).any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("transfer-encoding") && value.trim() == "chunked"
+        })
+    });
+    let body = if chunked {
+        dechunk(body).ok_or_else(|| invalid("malformed chunked response"))?
+    } else {
+        body.to_vec()
+    };
+
+    match status {
+        // 304 is an action that was already done, like stopping a stopped container
+        200..=299 | 304 => Ok(body),
+        _ => {
+            #[derive(Deserialize)]
+            struct ApiError {
+                message: String,
+            }
+            let message = serde_json::from_slice::<ApiError>(&body)
+                .map(|e| e.message)
+                .unwrap_or_else(|_| format!("Docker daemon returned HTTP {status}"));
+            let kind = match status {
+                404 => io::ErrorKind::NotFound,
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                _ => io::ErrorKind::Other,
+            };
+            Err(io::Error::new(kind, message))
+        }
+    }
+}
+
+/// Join the chunks of a `Transfer-Encoding: chunked` body
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+/// Whether `port` may belong to a container, so containers are only listed
+/// when some row could use them. Rows with a container cgroup always do.
+pub fn may_belong(port: &PortInfo) -> bool {
+    is_proxy(port) || container_namespace(port).is_some()
+}
+
+/// ID of the container each port's process runs in, from its cgroup, in
+/// order
+pub fn cgroup_ids(ports: &[PortInfo]) -> Vec<Option<String>> {
+    let mut by_pid: HashMap<u32, Option<String>> = HashMap::new();
+    ports
+        .iter()
+        .map(|p| {
+            by_pid
+                .entry(p.pid)
+                .or_insert_with(|| {
+                    let cgroup = fs::read_to_string(format!("/proc/{}/cgroup", p.pid)).ok()?;
+                    parse_cgroup(&cgroup)
+                })
+                .clone()
+        })
+        .collect()
+}
+
+/// Container ID in the contents of `/proc/<pid>/cgroup`: the
+/// `docker-<id>.scope` of the systemd cgroup driver, or the `docker/<id>`
+/// of the cgroupfs one
+fn parse_cgroup(cgroup: &str) -> Option<String> {
+    let is_id = |id: &str| id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit());
+    cgroup.lines().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        let parts: Vec<&str> = path.split('/').collect();
+        parts.iter().enumerate().find_map(|(i, part)| {
+            let id = match part.strip_prefix("docker-") {
+                Some(scope) => scope.strip_suffix(".scope")?,
+                None if *part == "docker" => parts.get(i + 1)?,
+                None => return None,
+            };
+            is_id(id).then(|| id.to_string())
+        })
+    })
+}
+
+fn is_proxy(port: &PortInfo) -> bool {
+    PROXIES.iter().any(|p| port.process_name.starts_with(p))
+}
+
+/// Short container ID from a `docker:<id>` namespace name
+fn container_namespace(port: &PortInfo) -> Option<&str> {
+    port.namespace.as_ref()?.name.strip_prefix("docker:")
+}
+
+/// The container `port` belongs to: the one whose network namespace it is
+/// in, the one whose cgroup `cgroup_id` its process runs in, or the one
+/// publishing it through a proxy process
+pub fn find<'a>(
+    containers: &'a [Container],
+    port: &PortInfo,
+    cgroup_id: Option<&str>,
+) -> Option<&'a Container> {
+    if let Some(id) = container_namespace(port) {
+        return containers.iter().find(|c| c.id.starts_with(id));
+    }
+    if let Some(id) = cgroup_id {
+        return containers.iter().find(|c| c.id == id);
+    }
+    if !is_proxy(port) {
+        return None;
+    }
+    containers.iter().find(|c| {
+        c.published.iter().any(|&(ip, public)| {
+            public == port.port && ip.is_none_or(|ip| ip.is_unspecified() || ip == port.address)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use port_checker::ports::Namespace;
+    use std::io::BufRead;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const CONTAINERS: &str = r#"[
+        {"Id": "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a", "Names": ["/shop-web-1"],
+         "Image": "nginx:1.27", "State": "running",
+         "Labels": {"com.docker.compose.project": "shop"},
+         "Ports": [{"IP": "0.0.0.0", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+                   {"IP": "::", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+                   {"PrivatePort": 9000, "Type": "tcp"}]},
+        {"Id": "9a8b7c6d5e4f9a8b7c6d5e4f", "Names": ["/cache"], "Image": "redis",
+         "Labels": null, "Ports": [{"IP": "127.0.0.1", "PrivatePort": 6379,
+                                    "PublicPort": 6380, "Type": "tcp"}]}
+    ]"#;
+
+    /// Stand-in daemon answering each connection with the next response,
+    /// and reporting the request lines it got
+    fn stand_in(responses: Vec<String>) -> (DockerClient, mpsc::Receiver<String>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("port-checker-docker-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join(format!("docker-{}.sock", responses.len()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = io::BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                tx.send(request_line.trim_end().to_string()).unwrap();
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (DockerClient::new(&socket), rx, dir)
+    }
+
+    fn proxy_port(port: u16, address: &str) -> PortInfo {
+        PortInfo::new(port, address.parse().unwrap(), 3100, "docker-proxy")
+    }
+
+    #[test]
+    fn test_containers_and_actions() {
+        let listing =
+            format!("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{CONTAINERS}");
+        let missing = "HTTP/1.0 404 Not Found\r\n\r\n{\"message\":\"No such container: nope\"}";
+        let (client, requests, dir) = stand_in(vec![
+            listing,
+            "HTTP/1.0 204 No Content\r\n\r\n".to_string(),
+            "HTTP/1.0 304 Not Modified\r\n\r\n".to_string(),
+            missing.to_string(),
+        ]);
+
+        let containers = client.containers().unwrap();
+        assert_eq!(requests.recv().unwrap(), "GET /containers/json HTTP/1.0");
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].name, "shop-web-1");
+        assert_eq!(containers[0].short_id(), "4f1c2a9e8b7d");
+        assert_eq!(containers[0].project.as_deref(), Some("shop"));
+        assert_eq!(containers[0].published.len(), 2);
+        assert_eq!(containers[1].project, None);
+
+        client
+            .act(&containers[0].id, ContainerAction::Restart)
+            .unwrap();
+        assert_eq!(
+            requests.recv().unwrap(),
+            "POST /containers/4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a/restart HTTP/1.0"
+        );
+        // Already stopped
+        client.act("cache", ContainerAction::Stop).unwrap();
+        let err = client.act("nope", ContainerAction::Stop).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No such container: nope");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_find() {
+        let summaries: Vec<Summary> = serde_json::from_str(CONTAINERS).unwrap();
+        let containers: Vec<Container> = summaries.into_iter().map(Container::from).collect();
+
+        let web = find(&containers, &proxy_port(8080, "::"), None).unwrap();
+        assert_eq!(web.name, "shop-web-1");
+        let cache = find(&containers, &proxy_port(6380, "127.0.0.1"), None).unwrap();
+        assert_eq!(cache.name, "cache");
+        assert_eq!(find(&containers, &proxy_port(6380, "0.0.0.0"), None), None);
+
+        // Ports of other processes are never a container's
+        let mut other = proxy_port(8080, "0.0.0.0");
+        other.process_name = "nginx".to_string();
+        assert!(!may_belong(&other));
+        assert_eq!(find(&containers, &other, None), None);
+
+        // Listeners inside the container's namespace
+        other.port = 80;
+        other.namespace = Some(Namespace {
+            inode: 4026532301,
+            name: "docker:4f1c2a9e8b7d".to_string(),
+        });
+        assert!(may_belong(&other));
+        assert_eq!(find(&containers, &other, None), Some(web));
+
+        // A host-network container's process, found by its cgroup
+        let mut host = proxy_port(6379, "127.0.0.1");
+        host.process_name = "redis-server".to_string();
+        let id = "9a8b7c6d5e4f9a8b7c6d5e4f";
+        assert_eq!(find(&containers, &host, Some(id)), Some(cache));
+        assert_eq!(find(&containers, &host, Some("0123")), None);
+    }
+
+    #[test]
+    fn test_parse_cgroup() {
+        let id = "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a";
+        // systemd cgroup driver, on cgroup v2 and v1
+        let v2 = format!("0::/system.slice/docker-{id}.scope\n");
+        assert_eq!(parse_cgroup(&v2).as_deref(), Some(id));
+        let v1 = format!("12:cpu,cpuacct:/system.slice/docker-{id}.scope\n");
+        assert_eq!(parse_cgroup(&v1).as_deref(), Some(id));
+        // cgroupfs driver
+        let cgroupfs = format!("0::/docker/{id}\n");
+        assert_eq!(parse_cgroup(&cgroupfs).as_deref(), Some(id));
+
+        assert_eq!(parse_cgroup("0::/system.slice/docker.service\n"), None);
+        assert_eq!(
+            parse_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+        assert_eq!(parse_cgroup("0::/docker/not-a-container-id\n"), None);
+    }
+
+    #[test]
+    fn test_dechunk() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n[{}]\r\n3\r\n,{}\r\n0\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), b"[{}],{}");
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+}
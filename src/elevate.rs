@@ -0,0 +1,216 @@
+use port_checker::ports::{self, ScanOptions, ScanReport};
+use port_checker::signal;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, ExitCode, Stdio};
+
+/// Environment variable to force the elevation tool: `sudo` or `pkexec`
+pub const TOOL_ENV: &str = "PORT_CHECKER_ELEVATE";
+
+/// Something to redo with root privileges
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Elevation {
+    Scan,
+    /// Signal every one of `pids`, with a single password prompt
+    Kill {
+        pids: Vec<u32>,
+        force: bool,
+    },
+}
+
+impl Elevation {
+    pub fn describe(&self) -> String {
+        match self {
+            Elevation::Scan => "rescan as root".to_string(),
+            Elevation::Kill { pids, force } => {
+                let verb = if *force { "force kill" } else { "terminate" };
+                match pids[..] {
+                    [pid] => format!("{verb} PID {pid} as root"),
+                    _ => format!("{verb} {} processes as root", pids.len()),
+                }
+            }
+        }
+    }
+}
+
+/// Result of an elevated kill, printed by the helper as JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum KillOutcome {
+    Killed,
+    Failed { message: String },
+}
+
+/// The elevation tool to use: `$PORT_CHECKER_ELEVATE`, else sudo, else pkexec
+pub fn tool() -> Option<String> {
+    if let Ok(tool) = env::var(TOOL_ENV) {
+        return Some(tool);
+    }
+    ["sudo", "pkexec"]
+        .into_iter()
+        .find(|tool| in_path(tool))
+        .map(str::to_string)
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Run this binary's `helper` command through sudo/pkexec and return its
+/// stdout. Stdin and stderr stay attached to the terminal for password prompts.
+fn run_helper(args: &[String]) -> Result<String, String> {
+    let tool = tool().ok_or("neither sudo nor pkexec found in PATH")?;
+    let exe = env::current_exe().map_err(|e| format!("cannot locate port-checker: {e}"))?;
+
+    eprintln!("Running {} as root via {tool}...", display_exe(&exe));
+    let output = Command::new(&tool)
+        .arg(&exe)
+        .arg("helper")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(|e| format!("running {tool}: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("{tool} exited with {}", output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|e| format!("helper output: {e}"))
+}
+
+fn display_exe(exe: &Path) -> String {
+    exe.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| exe.display().to_string())
+}
+
+/// Scan with root privileges, reading the socket table like `options`
+pub fn scan(options: &ScanOptions) -> Result<ScanReport, String> {
+    // sudo resets the environment, so pass the collector explicitly
+    let mut args = vec![
+        "scan".to_string(),
+        "--collector".to_string(),
+        options.collector.name().to_string(),
+    ];
+    if options.namespaces {
+        args.push("--namespaces".to_string());
+    }
+    let output = run_helper(&args)?;
+    serde_json::from_str(&output).map_err(|e| format!("helper output: {e}"))
+}
+
+/// Signal each of `pids` with root privileges
+pub fn kill(pids: &[u32], force: bool) -> Result<(), String> {
+    let mut args = vec!["kill".to_string()];
+    args.extend(pids.iter().map(u32::to_string));
+    if force {
+        args.push("--force".to_string());
+    }
+    match serde_json::from_str(&run_helper(&args)?) {
+        Ok(KillOutcome::Killed) => Ok(()),
+        Ok(KillOutcome::Failed { message }) => Err(message),
+        Err(e) => Err(format!("helper output: {e}")),
+    }
+}
+
+/// Signal `pid` only if it owns a listening TCP or Unix socket in any
+/// network namespace, so the helper cannot be used to kill arbitrary
+/// processes when allowed in sudoers
+fn kill_listener(pid: u32, force: bool) -> KillOutcome {
+    let mut options = ScanOptions::default();
+    options.unix = true;
+    options.namespaces = true;
+    match ports::scan(&options) {
+        Ok(report)
+            if report.ports.iter().any(|p| p.pid == pid)
+                || report.unix_sockets.iter().any(|u| u.pid == pid) => {}
+        Ok(_) => {
+            return KillOutcome::Failed {
+                message: format!("PID {pid} does not own a listening socket"),
+            }
+        }
+        Err(e) => {
+            return KillOutcome::Failed {
+                message: e.to_string(),
+            }
+        }
+    }
+    match signal::send_signal(pid, force) {
+        Ok(()) => KillOutcome::Killed,
+        Err(e) => KillOutcome::Failed {
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Entry point of the `helper` command run under sudo/pkexec. Prints one JSON
+/// document on stdout for the unprivileged TUI to parse. Scans read the
+/// socket table with the collector of `options`.
+pub fn run_helper_command(args: &[String], options: &ScanOptions) -> ExitCode {
+    let json = match args {
+        [cmd] if cmd == "scan" => {
+            // Only the TUI runs the helper, which also lists Unix sockets
+            let mut options = options.clone();
+            options.unix = true;
+            match ports::scan(&options) {
+                Ok(report) => serde_json::to_string(&report),
+                Err(e) => {
+                    eprintln!("Scan failed: {e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        [cmd, rest @ ..] if cmd == "kill" && !rest.is_empty() => {
+            let force = rest.iter().any(|a| a == "--force");
+            let errors: Vec<String> = rest
+                .iter()
+                .filter(|a| *a != "--force")
+                .filter_map(|pid| {
+                    let outcome = match pid.parse() {
+                        Ok(pid) => kill_listener(pid, force),
+                        Err(_) => KillOutcome::Failed {
+                            message: format!("invalid PID '{pid}'"),
+                        },
+                    };
+                    match outcome {
+                        KillOutcome::Killed => None,
+                        KillOutcome::Failed { message } => Some(message),
+                    }
+                })
+                .collect();
+            let outcome = if errors.is_empty() {
+                KillOutcome::Killed
+            } else {
+                KillOutcome::Failed {
+                    message: errors.join("; "),
+                }
+            };
+            serde_json::to_string(&outcome)
+        }
+        _ => {
+            eprintln!("usage: port-checker helper scan [--collector NAME] [--namespaces] | kill PID... [--force]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match json {
+        Ok(json) => {
+            let mut stdout = io::stdout();
+            if writeln!(stdout, "{json}")
+                .and_then(|()| stdout.flush())
+                .is_err()
+            {
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Encoding result: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
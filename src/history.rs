@@ -0,0 +1,377 @@
+use crate::snapshot::{format_timestamp, unix_now};
+use port_checker::ports::{ListenerKey, PortInfo, ScanOptions, Scanner};
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Default seconds between scans in daemon mode
+pub const DEFAULT_INTERVAL_SECS: u64 = 10;
+
+/// Default number of days of history kept
+pub const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+/// How often the daemon drops events older than the retention period
+const COMPACT_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Open,
+    Close,
+}
+
+/// A listener appearing or disappearing between two scans
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    /// Seconds since the Unix epoch of the scan that saw the change
+    pub at: u64,
+    pub kind: EventKind,
+    pub port: PortInfo,
+}
+
+/// A listener's lifetime reconstructed from its open and close events
+#[derive(Clone, Debug)]
+pub struct Lifespan {
+    pub port: PortInfo,
+    pub opened: u64,
+    /// `None` while the listener is still open at the end of the history
+    pub closed: Option<u64>,
+}
+
+impl Lifespan {
+    pub fn contains(&self, at: u64) -> bool {
+        self.opened <= at && self.closed.is_none_or(|closed| at < closed)
+    }
+}
+
+/// History file location: `$XDG_DATA_HOME/port-checker/history.jsonl`,
+/// falling back to `~/.local/share/port-checker/history.jsonl`
+pub fn default_path() -> PathBuf {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    data_dir.join("port-checker").join("history.jsonl")
+}
+
+/// Listener open/close events, stored on disk as one JSON event per line
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    pub events: Vec<Event>,
+}
+
+impl History {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        let mut events = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Event = serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: {e}", path.display(), i + 1))?;
+            events.push(event);
+        }
+        events.sort_by_key(|e| e.at);
+        Ok(Self { events })
+    }
+
+    /// First and last event times
+    pub fn span(&self) -> Option<(u64, u64)> {
+        Some((self.events.first()?.at, self.events.last()?.at))
+    }
+
+    pub fn lifespans(&self) -> Vec<Lifespan> {
+        let mut open: BTreeMap<ListenerKey, Lifespan> = BTreeMap::new();
+        let mut done = Vec::new();
+        for event in &self.events {
+            let key = event.port.key();
+            match event.kind {
+                EventKind::Open => {
+                    open.entry(key).or_insert_with(|| Lifespan {
+                        port: event.port.clone(),
+                        opened: event.at,
+                        closed: None,
+                    });
+                }
+                EventKind::Close => {
+                    if let Some(mut span) = open.remove(&key) {
+                        span.closed = Some(event.at);
+                        done.push(span);
+                    }
+                }
+            }
+        }
+        done.extend(open.into_values());
+        done.sort_by_key(|s| (s.port.port, s.opened));
+        done
+    }
+
+    /// The listeners that were open at `at`, like a scan taken at that moment
+    pub fn state_at(&self, at: u64) -> Vec<PortInfo> {
+        let mut ports: Vec<PortInfo> = self
+            .lifespans()
+            .into_iter()
+            .filter(|s| s.contains(at))
+            .map(|s| s.port)
+            .collect();
+        ports.sort_by_key(|p| p.key());
+        ports
+    }
+
+    pub fn prev_event(&self, at: u64) -> Option<u64> {
+        self.events.iter().rev().map(|e| e.at).find(|&t| t < at)
+    }
+
+    pub fn next_event(&self, at: u64) -> Option<u64> {
+        self.events.iter().map(|e| e.at).find(|&t| t > at)
+    }
+
+    /// Drop events older than `cutoff`, except opens of listeners that were
+    /// still open at `cutoff`
+    fn compact(&mut self, cutoff: u64) {
+        let still_open: Vec<(ListenerKey, u64)> = self
+            .lifespans()
+            .into_iter()
+            .filter(|s| s.opened < cutoff && s.contains(cutoff))
+            .map(|s| (s.port.key(), s.opened))
+            .collect();
+        self.events.retain(|e| {
+            e.at >= cutoff
+                || (e.kind == EventKind::Open && still_open.contains(&(e.port.key(), e.at)))
+        });
+    }
+
+    fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = path.with_extension("jsonl.tmp");
+        let mut file = File::create(&tmp)?;
+        for event in &self.events {
+            writeln!(file, "{}", serde_json::to_string(event)?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Turns successive scans into open/close events
+#[derive(Default)]
+pub struct Recorder {
+    open: BTreeMap<ListenerKey, PortInfo>,
+}
+
+impl Recorder {
+    /// Start from the listeners a history says are still open
+    pub fn resume(history: &History) -> Self {
+        let open = history
+            .lifespans()
+            .into_iter()
+            .filter(|s| s.closed.is_none())
+            .map(|s| (s.port.key(), s.port))
+            .collect();
+        Self { open }
+    }
+
+    pub fn observe(&mut self, at: u64, ports: &[PortInfo]) -> Vec<Event> {
+        let current: BTreeMap<ListenerKey, &PortInfo> =
+            ports.iter().map(|p| (p.key(), p)).collect();
+
+        let mut events = Vec::new();
+        self.open.retain(|key, port| {
+            let still_open = current.contains_key(key);
+            if !still_open {
+                events.push(Event {
+                    at,
+                    kind: EventKind::Close,
+                    port: port.clone(),
+                });
+            }
+            still_open
+        });
+        for (key, port) in current {
+            if let Entry::Vacant(entry) = self.open.entry(key) {
+                entry.insert(port.clone());
+                events.push(Event {
+                    at,
+                    kind: EventKind::Open,
+                    port: port.clone(),
+                });
+            }
+        }
+        events
+    }
+}
+
+/// The history file as the daemon writes it: new events are appended to the
+/// file and kept in memory, and events past the retention period are dropped
+/// every `COMPACT_INTERVAL_SECS` by rewriting the file
+struct Journal {
+    path: PathBuf,
+    history: History,
+    file: File,
+    retention_secs: u64,
+    compacted_at: u64,
+}
+
+impl Journal {
+    fn open(
+        path: &Path,
+        retention_days: u64,
+        now: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut history = if path.exists() {
+            History::load(path)?
+        } else {
+            History::default()
+        };
+        let retention_secs = retention_days * 86_400;
+        history.compact(now.saturating_sub(retention_secs));
+        history.write(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            history,
+            file: OpenOptions::new().append(true).open(path)?,
+            retention_secs,
+            compacted_at: now,
+        })
+    }
+
+    /// Append `events` seen at `now`, compacting first when it is due
+    fn record(&mut self, now: u64, events: Vec<Event>) -> Result<(), Box<dyn std::error::Error>> {
+        for event in &events {
+            writeln!(self.file, "{}", serde_json::to_string(event)?)?;
+        }
+        self.file.flush()?;
+        self.history.events.extend(events);
+        if now >= self.compacted_at + COMPACT_INTERVAL_SECS {
+            self.compact(now)?;
+        }
+        Ok(())
+    }
+
+    fn compact(&mut self, now: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.history
+            .compact(now.saturating_sub(self.retention_secs));
+        self.history.write(&self.path)?;
+        // The rename replaced the file the old handle appended to
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.compacted_at = now;
+        Ok(())
+    }
+}
+
+/// Scan with `options` every `interval` and append listener changes to the
+/// history at `path`. Runs until the process is stopped.
+pub fn run_daemon(
+    path: &Path,
+    interval: Duration,
+    retention_days: u64,
+    options: &ScanOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut journal = Journal::open(path, retention_days, unix_now())?;
+    let mut recorder = Recorder::resume(&journal.history);
+    eprintln!(
+        "Recording listener history to {} every {}s",
+        path.display(),
+        interval.as_secs()
+    );
+
+    let mut scanner = Scanner::new();
+    loop {
+        match scanner.scan(options) {
+            Ok(report) => {
+                let now = unix_now();
+                journal.record(now, recorder.observe(now, &report.ports))?;
+            }
+            Err(e) => eprintln!("{}: scan failed: {e}", format_timestamp(unix_now())),
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(port: u16, pid: u32) -> PortInfo {
+        PortInfo::new(port, "127.0.0.1".parse().unwrap(), pid, "node")
+    }
+
+    #[test]
+    fn test_recorder_and_state_at() {
+        let mut recorder = Recorder::default();
+        let mut history = History::default();
+        history.events.extend(recorder.observe(100, &[port(22, 1)]));
+        history
+            .events
+            .extend(recorder.observe(200, &[port(22, 1), port(9229, 7)]));
+        history.events.extend(recorder.observe(300, &[port(22, 1)]));
+        assert_eq!(history.events.len(), 3);
+
+        let ports_at = |at| -> Vec<u16> { history.state_at(at).iter().map(|p| p.port).collect() };
+        assert_eq!(ports_at(150), vec![22]);
+        assert_eq!(ports_at(250), vec![22, 9229]);
+        assert_eq!(ports_at(300), vec![22]);
+
+        history.compact(250);
+        assert_eq!(history.events.len(), 3);
+        history.compact(301);
+        assert_eq!(history.events.len(), 1);
+        assert_eq!(
+            Recorder::resume(&history)
+                .observe(400, &[port(22, 1)])
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_journal_compacts_while_recording() {
+        let path =
+            std::env::temp_dir().join(format!("port-checker-history-{}.jsonl", std::process::id()));
+        let day = 86_400;
+        let mut recorder = Recorder::default();
+        let mut journal = Journal::open(&path, 1, day).unwrap();
+        journal
+            .record(day, recorder.observe(day, &[port(22, 1), port(3000, 7)]))
+            .unwrap();
+        journal
+            .record(day + 60, recorder.observe(day + 60, &[port(22, 1)]))
+            .unwrap();
+        assert_eq!(History::load(&path).unwrap().events.len(), 3);
+
+        // A day later the closed listener's events are past retention, but
+        // the open of the listener still running is kept
+        let now = 2 * day + 120;
+        journal
+            .record(now, recorder.observe(now, &[port(22, 1), port(8080, 9)]))
+            .unwrap();
+        let kinds = |history: &History| -> Vec<(u16, EventKind)> {
+            history
+                .events
+                .iter()
+                .map(|e| (e.port.port, e.kind))
+                .collect()
+        };
+        let expected = vec![(22, EventKind::Open), (8080, EventKind::Open)];
+        assert_eq!(kinds(&journal.history), expected);
+        assert_eq!(kinds(&History::load(&path).unwrap()), expected);
+
+        // Appends go to the rewritten file
+        journal
+            .record(now + 10, recorder.observe(now + 10, &[port(22, 1)]))
+            .unwrap();
+        assert_eq!(History::load(&path).unwrap().events.len(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,28 @@
+//! Scan listening TCP sockets and the processes that own them.
+//!
+//! This is the data layer behind the `port-checker` TUI. It can be embedded
+//! in other tools:
+//!
+//! ```no_run
+//! use port_checker::ports::{scan, summarize_warnings, ScanOptions};
+//!
+//! let mut options = ScanOptions::default();
+//! options.ipv6 = false;
+//! let report = scan(&options).expect("scan failed");
+//! for p in &report.ports {
+//!     println!("{} {} {}", p.port, p.pid, p.process_name);
+//! }
+//! for warning in summarize_warnings(&report.warnings) {
+//!     eprintln!("warning: {warning}");
+//! }
+//! ```
+//!
+//! `PortInfo` implements serde's `Serialize` and `Deserialize`, and the
+//! [`signal`] module terminates or kills the owning processes.
+
+#[cfg(target_os = "linux")]
+mod netns;
+pub mod ports;
+pub mod signal;
+#[cfg(target_os = "linux")]
+mod sock_diag;
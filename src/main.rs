@@ -1,28 +1,174 @@
+mod agent;
 mod app;
-mod ports;
+mod backend;
+mod cli;
+mod docker;
+mod elevate;
+mod history;
+mod metrics;
+mod orphan;
+mod proctree;
+mod project;
+mod recognize;
+mod snapshot;
+mod stale;
+mod systemd;
 mod ui;
 
 use std::io;
 use std::panic;
+use std::process::ExitCode;
 
+use color_eyre::eyre::eyre;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use nix::sys::signal::{kill, Signal};
-use nix::unistd::Pid;
 use ratatui::prelude::*;
 
 use app::App;
+use cli::Command;
+use docker::ContainerAction;
+use elevate::Elevation;
+use port_checker::ports::{self, ScanOptions, ScanReport};
+use snapshot::Snapshot;
+use systemd::UnitAction;
 
 /// Poll timeout for event loop (milliseconds)
 const EVENT_POLL_TIMEOUT_MS: u64 = 100;
 
-fn main() -> color_eyre::Result<()> {
+/// Exit status when `diff` finds differences, like diff(1)
+const EXIT_DIFFERENT: u8 = 1;
+
+/// Exit status for errors, including scans that failed outright
+const EXIT_ERROR: u8 = 2;
+
+/// Exit status when a scan succeeded but some sockets or processes were hidden
+const EXIT_PARTIAL: u8 = 3;
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(code) => code,
+        Err(report) => {
+            eprintln!("Error: {report:?}");
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn try_main() -> color_eyre::Result<ExitCode> {
     // Install color-eyre for better error reporting
     color_eyre::install()?;
 
+    let cli::Cli { command, options } = cli::parse(std::env::args().skip(1))?;
+    match command {
+        Command::Tui => run_tui(App::new(options.clone()), &options),
+        Command::Load { path } => run_tui(App::from_snapshot(load_snapshot(&path)?), &options),
+        Command::Save { path } => {
+            let report = scan_for_cli(&options)?;
+            let count = report.ports.len();
+            let partial = report.is_partial();
+            Snapshot::new(report.ports)
+                .save(&path)
+                .map_err(|e| eyre!("Saving {}: {e}", path.display()))?;
+            println!("Saved {count} ports to {}", path.display());
+            Ok(exit_code(false, partial))
+        }
+        Command::Diff { old, new, tui } => {
+            let baseline = load_snapshot(&old)?;
+            let against = new.as_deref().map(load_snapshot).transpose()?;
+            if tui {
+                return run_tui(App::from_diff(baseline, against, options.clone()), &options);
+            }
+
+            let (current, partial) = match &against {
+                Some(snapshot) => (snapshot.ports.clone(), false),
+                None => {
+                    let report = scan_for_cli(&options)?;
+                    let partial = report.is_partial();
+                    (report.ports, partial)
+                }
+            };
+            let entries = snapshot::diff(&baseline.ports, &current);
+            for line in snapshot::format_diff(&entries) {
+                println!("{line}");
+            }
+
+            let different = entries
+                .iter()
+                .any(|e| e.kind != snapshot::DiffKind::Unchanged);
+            Ok(exit_code(different, partial))
+        }
+        Command::Daemon {
+            history,
+            interval_secs,
+            retention_days,
+        } => history::run_daemon(
+            &history,
+            std::time::Duration::from_secs(interval_secs),
+            retention_days,
+            &options,
+        )
+        .map(|()| ExitCode::SUCCESS)
+        .map_err(|e| eyre!("History daemon: {e}")),
+        Command::Timeline { history } => {
+            let history = if history.exists() {
+                history::History::load(&history)
+                    .map_err(|e| eyre!("Loading {}: {e}", history.display()))?
+            } else {
+                history::History::default()
+            };
+            run_tui(App::from_history(history), &options)
+        }
+        Command::ServeMetrics { listen, expected } => metrics::serve(listen, &expected, &options)
+            .map(|()| ExitCode::SUCCESS)
+            .map_err(|e| eyre!("Metrics server: {e}")),
+        Command::Agent { listen, token } => agent::serve(&listen, token, options)
+            .map(|()| ExitCode::SUCCESS)
+            .map_err(|e| eyre!("Agent: {e}")),
+        Command::Connect { agents, token } => {
+            let clients = agents
+                .into_iter()
+                .map(|addr| agent::AgentClient::new(addr, token.clone()))
+                .collect();
+            run_tui(App::from_agents(clients), &options)
+        }
+        Command::Helper { args } => Ok(elevate::run_helper_command(&args, &options)),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Scan for a CLI command, printing what the scan could not see to stderr
+fn scan_for_cli(options: &ScanOptions) -> color_eyre::Result<ScanReport> {
+    let report = ports::scan(options).map_err(|e| eyre!("Scan failed: {e}"))?;
+    for line in ports::summarize_warnings(&report.warnings) {
+        eprintln!("warning: {line}");
+    }
+    Ok(report)
+}
+
+/// Differences take precedence over partial scans, so scripts comparing
+/// snapshots keep working when not run as root
+fn exit_code(different: bool, partial: bool) -> ExitCode {
+    if different {
+        ExitCode::from(EXIT_DIFFERENT)
+    } else if partial {
+        ExitCode::from(EXIT_PARTIAL)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn load_snapshot(path: &std::path::Path) -> color_eyre::Result<Snapshot> {
+    Snapshot::load(path).map_err(|e| eyre!("Loading {}: {e}", path.display()))
+}
+
+/// Run the TUI on `app`; root rescans read the socket table like `options`
+fn run_tui(mut app: App, options: &ScanOptions) -> color_eyre::Result<ExitCode> {
     // Set up panic hook to restore terminal on panic
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -38,29 +184,34 @@ fn main() -> color_eyre::Result<()> {
     let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
 
     // Run app
-    let mut app = App::new();
-    let result = run(&mut terminal, &mut app);
+    let result = run(&mut terminal, &mut app, options);
 
     // Restore terminal
     disable_raw_mode()?;
     io::stdout().execute(LeaveAlternateScreen)?;
 
-    result
+    result.map(|()| ExitCode::SUCCESS)
 }
 
 fn run(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
+    options: &ScanOptions,
 ) -> color_eyre::Result<()> {
     loop {
         app.clear_old_status();
+        app.poll_scan();
 
         terminal.draw(|frame| ui::render(frame, app))?;
 
         if event::poll(std::time::Duration::from_millis(EVENT_POLL_TIMEOUT_MS))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    if app.show_terminate_popup {
+                    if app.elevation_prompt.is_some() {
+                        handle_elevation_key(key.code, app);
+                    } else if app.cleanup_prompt.is_some() {
+                        handle_cleanup_key(key.code, app);
+                    } else if app.show_terminate_popup {
                         handle_popup_key(key.code, app);
                     } else if handle_main_key(key.code, app) {
                         return Ok(());
@@ -68,12 +219,91 @@ fn run(
                 }
             }
         }
+
+        if let Some(elevation) = app.pending_elevation.take() {
+            run_elevated(terminal, app, elevation, options)?;
+        }
+        if let Some(unit) = app.pending_unit_status.take() {
+            run_unit_status(terminal, app, &unit)?;
+        }
+    }
+}
+
+/// Leave the TUI for `systemctl status`, which pages its output, and come
+/// back when the pager is closed
+fn run_unit_status(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    unit: &systemd::Unit,
+) -> color_eyre::Result<()> {
+    disable_raw_mode()?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+
+    let result = systemd::command(unit, UnitAction::Status).status();
+
+    io::stdout().execute(EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+
+    // Status exits non-zero for inactive units, which is not a failure here
+    if let Err(e) = result {
+        app.set_status(&format!("Failed to run systemctl: {e}"));
+    }
+    Ok(())
+}
+
+/// Leave the TUI so sudo/pkexec can prompt for a password, run `elevation`
+/// through the helper, then restore the TUI and show the result
+fn run_elevated(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    elevation: Elevation,
+    options: &ScanOptions,
+) -> color_eyre::Result<()> {
+    disable_raw_mode()?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+
+    let result = match &elevation {
+        Elevation::Scan => elevate::scan(options).map(|report| app.apply_elevated_scan(report)),
+        Elevation::Kill { pids, force } => {
+            elevate::kill(pids, *force).map(|()| app.killed(pids, *force))
+        }
+    };
+
+    io::stdout().execute(EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+
+    if let Err(e) = result {
+        app.set_status(&format!("Failed to {}: {e}", elevation.describe()));
+    }
+    Ok(())
+}
+
+/// Handle keyboard input in the elevation prompt
+fn handle_elevation_key(code: KeyCode, app: &mut App) {
+    match code {
+        KeyCode::Char('y') | KeyCode::Enter => app.confirm_elevation(),
+        KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('q') => app.dismiss_elevation(),
+        _ => {}
+    }
+}
+
+fn handle_cleanup_key(code: KeyCode, app: &mut App) {
+    match code {
+        KeyCode::Char('y') | KeyCode::Enter => app.confirm_cleanup(),
+        KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('q') => app.dismiss_cleanup(),
+        _ => {}
     }
 }
 
 /// Handle keyboard input in main view. Returns true if app should quit.
 fn handle_main_key(code: KeyCode, app: &mut App) -> bool {
     match code {
+        KeyCode::Esc if app.is_scanning() => {
+            app.cancel_scan();
+            false
+        }
         KeyCode::Char('q') | KeyCode::Esc => true,
         KeyCode::Char('r') => {
             app.refresh();
@@ -99,10 +329,70 @@ fn handle_main_key(code: KeyCode, app: &mut App) -> bool {
             app.open_terminate_popup();
             false
         }
+        KeyCode::Char('s') => {
+            app.save_snapshot();
+            false
+        }
+        KeyCode::Char('E') => {
+            app.offer_elevation(Elevation::Scan);
+            false
+        }
+        KeyCode::Char('u') => {
+            app.toggle_view();
+            false
+        }
+        KeyCode::Char('n') => {
+            app.cycle_namespace();
+            false
+        }
+        KeyCode::Char('m') => {
+            app.toggle_mine();
+            false
+        }
+        KeyCode::Char('o') => {
+            app.toggle_orphans();
+            false
+        }
+        KeyCode::Char('O') => {
+            app.offer_cleanup();
+            false
+        }
+        KeyCode::Char('g') => {
+            app.cycle_grouping();
+            false
+        }
+        KeyCode::Char(' ') => {
+            app.toggle_group();
+            false
+        }
         KeyCode::Home => {
             app.scroll_offset = 0;
             false
         }
+        KeyCode::Char('[') => {
+            app.timeline_step(-1);
+            false
+        }
+        KeyCode::Char(']') => {
+            app.timeline_step(1);
+            false
+        }
+        KeyCode::Char('{') => {
+            app.timeline_jump(false);
+            false
+        }
+        KeyCode::Char('}') => {
+            app.timeline_jump(true);
+            false
+        }
+        KeyCode::Char('-') => {
+            app.timeline_zoom(false);
+            false
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            app.timeline_zoom(true);
+            false
+        }
         _ => false,
     }
 }
@@ -111,48 +401,66 @@ fn handle_main_key(code: KeyCode, app: &mut App) -> bool {
 fn handle_popup_key(code: KeyCode, app: &mut App) {
     match code {
         KeyCode::Esc | KeyCode::Char('q') => app.close_popup(),
+        KeyCode::Up => app.tree_prev(),
+        KeyCode::Down => app.tree_next(),
         KeyCode::Left | KeyCode::Char('h') => app.popup_prev(),
         KeyCode::Right | KeyCode::Char('l') => app.popup_next(),
         KeyCode::Tab => app.popup_next(),
         KeyCode::BackTab => app.popup_prev(),
         KeyCode::Enter => {
             if let Some((pid, force)) = app.execute_popup_action() {
-                kill_process(pid, force, app);
+                app.kill_process(pid, force);
             }
         }
+        KeyCode::Char('t') | KeyCode::Char('k') if app.is_read_only() => {
+            app.close_popup();
+            app.set_status("Read-only view: processes cannot be killed");
+        }
+        KeyCode::Char('t') | KeyCode::Char('k') if app.get_selected_project_group().is_some() => {
+            app.close_popup();
+            app.kill_project(code == KeyCode::Char('k'));
+        }
+        // A project row's only actions stop every process of the project
+        KeyCode::Char('s') | KeyCode::Char('r') | KeyCode::Char('i')
+            if app.get_selected_project_group().is_some() => {}
         KeyCode::Char('t') => {
-            if let Some(pid) = app.get_selected_port().map(|p| p.pid) {
+            if let Some(pid) = app.get_selected_pid() {
                 app.close_popup();
-                kill_process(pid, false, app);
+                app.kill_process(pid, false);
             }
         }
         KeyCode::Char('k') => {
-            if let Some(pid) = app.get_selected_port().map(|p| p.pid) {
+            if let Some(pid) = app.get_selected_pid() {
                 app.close_popup();
-                kill_process(pid, true, app);
+                app.kill_process(pid, true);
             }
         }
-        _ => {}
-    }
-}
-
-/// Kill a process using proper signal handling via nix
-fn kill_process(pid: u32, force: bool, app: &mut App) {
-    let signal = if force { Signal::SIGKILL } else { Signal::SIGTERM };
-    let nix_pid = Pid::from_raw(pid as i32);
-
-    match kill(nix_pid, signal) {
-        Ok(()) => {
-            let msg = if force {
-                format!("Force killed PID {pid}")
+        KeyCode::Char('s') | KeyCode::Char('r') if app.get_selected_container().is_some() => {
+            let action = if code == KeyCode::Char('s') {
+                ContainerAction::Stop
             } else {
-                format!("Terminated PID {pid}")
+                ContainerAction::Restart
             };
-            app.set_status(&msg);
-            app.refresh();
+            app.close_popup();
+            app.container_action(action);
         }
-        Err(e) => {
-            app.set_status(&format!("Failed: {e}"));
+        KeyCode::Char('s') | KeyCode::Char('r') if app.get_selected_unit().is_some() => {
+            let action = if code == KeyCode::Char('s') {
+                UnitAction::Stop
+            } else {
+                UnitAction::Restart
+            };
+            app.close_popup();
+            app.unit_action(action);
         }
+        KeyCode::Char('r') if app.is_selected_stale() => {
+            app.close_popup();
+            app.restart_process();
+        }
+        KeyCode::Char('i') => {
+            app.close_popup();
+            app.show_unit_status();
+        }
+        _ => {}
     }
 }
@@ -0,0 +1,406 @@
+use port_checker::ports::{PortInfo, ScanOptions, ScanWarning, Scanner};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+/// Default address for `serve-metrics`, loopback only
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9464";
+
+/// How long a scraper may take to send its request. Requests are served one
+/// at a time, so a client that stalls holds up the others for this long.
+const REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Most bytes read of a request line and its headers
+const MAX_HEADER_BYTES: u64 = 8192;
+
+/// `kind` labels of `port_checker_scan_warnings`, always exported so that
+/// alerts see 0 rather than no series
+const WARNING_KINDS: [&str; 4] = [
+    "hidden_socket",
+    "hidden_unix_socket",
+    "restricted_process",
+    "socket_error",
+];
+
+/// Results of one scan, ready to be rendered as Prometheus text
+struct Scrape {
+    ports: Vec<PortInfo>,
+    /// What the scan could not see
+    warnings: Vec<ScanWarning>,
+    duration: Duration,
+    ok: bool,
+}
+
+/// Serve `/metrics` on `addr` until the process is stopped. Every scrape runs
+/// a fresh scan with `options`; `expected` ports get an up/down gauge for
+/// alerting.
+pub fn serve(
+    addr: SocketAddr,
+    expected: &[u16],
+    options: &ScanOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+
+    let mut scanner = Scanner::new();
+    let mut scan_errors: u64 = 0;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Accept failed: {e}");
+                continue;
+            }
+        };
+        if let Err(e) = handle_connection(stream, &mut scanner, options, expected, &mut scan_errors)
+        {
+            eprintln!("Request failed: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    scanner: &mut Scanner,
+    options: &ScanOptions,
+    expected: &[u16],
+    scan_errors: &mut u64,
+) -> io::Result<()> {
+    let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+    stream.set_write_timeout(Some(timeout))?;
+    let deadline = Deadline {
+        stream: &stream,
+        at: Instant::now() + timeout,
+    };
+    let mut reader = BufReader::new(deadline.take(MAX_HEADER_BYTES));
+
+    let mut request_line = String::new();
+    read_header_line(&mut reader, &mut request_line)?;
+    // Drain headers; the request body is ignored
+    let mut header = String::new();
+    while read_header_line(&mut reader, &mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let scrape = scrape(scanner, options);
+            if !scrape.ok {
+                *scan_errors += 1;
+            }
+            (
+                "200 OK",
+                "text/plain; version=0.0.4",
+                render(&scrape, expected, *scan_errors),
+            )
+        }
+        ("GET", "/") => (
+            "200 OK",
+            "text/html",
+            "<a href=\"/metrics\">metrics</a>\n".to_string(),
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Reads from a stream until a deadline for the whole request, rather than
+/// allowing a fresh timeout for every read
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    at: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.at.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read one line of the request head, failing once it outgrows
+/// `MAX_HEADER_BYTES`
+fn read_header_line<R: Read>(
+    reader: &mut BufReader<io::Take<R>>,
+    line: &mut String,
+) -> io::Result<usize> {
+    let read = reader.read_line(line)?;
+    if reader.get_ref().limit() == 0 && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request headers too long",
+        ));
+    }
+    Ok(read)
+}
+
+fn scrape(scanner: &mut Scanner, options: &ScanOptions) -> Scrape {
+    let start = Instant::now();
+    let (ports, warnings, ok) = match scanner.scan(options) {
+        Ok(report) => (report.ports, report.warnings, true),
+        Err(e) => {
+            eprintln!("Scan failed: {e}");
+            (vec![], vec![], false)
+        }
+    };
+    let duration = start.elapsed();
+    Scrape {
+        ports,
+        warnings,
+        duration,
+        ok,
+    }
+}
+
+/// `kind` label of a scan warning
+fn warning_kind(warning: &ScanWarning) -> &'static str {
+    match warning {
+        ScanWarning::HiddenSocket { .. } => "hidden_socket",
+        ScanWarning::HiddenUnixSocket { .. } => "hidden_unix_socket",
+        ScanWarning::RestrictedProcess { .. } => "restricted_process",
+        ScanWarning::SocketError(_) => "socket_error",
+        _ => "other",
+    }
+}
+
+/// User name of a listener's process, or its UID when it has no name
+fn user_of(port: &PortInfo) -> String {
+    match (&port.user, port.uid) {
+        (Some(name), _) => name.clone(),
+        (None, Some(uid)) => uid.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+fn render(scrape: &Scrape, expected: &[u16], scan_errors: u64) -> String {
+    let mut out = String::new();
+
+    // PIDs change on every restart, so they are kept out of this series
+    header(
+        &mut out,
+        "port_checker_listener",
+        "gauge",
+        "Number of listening TCP sockets per port, address, process and user",
+    );
+    let mut listeners: BTreeMap<(u16, IpAddr, &str, String), usize> = BTreeMap::new();
+    for p in &scrape.ports {
+        let key = (p.port, p.address, p.process_name.as_str(), user_of(p));
+        *listeners.entry(key).or_default() += 1;
+    }
+    for ((port, address, process, user), count) in listeners {
+        let _ = writeln!(
+            out,
+            "port_checker_listener{{port=\"{port}\",address=\"{address}\",process=\"{}\",user=\"{}\"}} {count}",
+            escape(process),
+            escape(&user)
+        );
+    }
+
+    header(
+        &mut out,
+        "port_checker_listener_info",
+        "gauge",
+        "Process ID owning each listening TCP socket",
+    );
+    for p in &scrape.ports {
+        let _ = writeln!(
+            out,
+            "port_checker_listener_info{{port=\"{}\",address=\"{}\",pid=\"{}\"}} 1",
+            p.port, p.address, p.pid
+        );
+    }
+
+    header(
+        &mut out,
+        "port_checker_listeners_by_process",
+        "gauge",
+        "Number of listening TCP sockets per process and user",
+    );
+    let mut by_process: BTreeMap<(&str, String), usize> = BTreeMap::new();
+    for p in &scrape.ports {
+        *by_process.entry((&p.process_name, user_of(p))).or_default() += 1;
+    }
+    for ((process, user), count) in by_process {
+        let _ = writeln!(
+            out,
+            "port_checker_listeners_by_process{{process=\"{}\",user=\"{}\"}} {count}",
+            escape(process),
+            escape(&user)
+        );
+    }
+
+    header(
+        &mut out,
+        "port_checker_listeners",
+        "gauge",
+        "Total number of listening TCP sockets",
+    );
+    let _ = writeln!(out, "port_checker_listeners {}", scrape.ports.len());
+
+    if !expected.is_empty() {
+        header(
+            &mut out,
+            "port_checker_expected_port_up",
+            "gauge",
+            "Whether a port passed with --expect has a listener (1) or not (0)",
+        );
+        for port in expected {
+            let up = scrape.ports.iter().any(|p| p.port == *port);
+            let _ = writeln!(
+                out,
+                "port_checker_expected_port_up{{port=\"{port}\"}} {}",
+                u8::from(up)
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "port_checker_scan_duration_seconds",
+        "gauge",
+        "Time taken by the scan behind this scrape",
+    );
+    let _ = writeln!(
+        out,
+        "port_checker_scan_duration_seconds {}",
+        scrape.duration.as_secs_f64()
+    );
+
+    header(
+        &mut out,
+        "port_checker_scan_success",
+        "gauge",
+        "Whether the scan behind this scrape succeeded",
+    );
+    let _ = writeln!(out, "port_checker_scan_success {}", u8::from(scrape.ok));
+
+    header(
+        &mut out,
+        "port_checker_scan_warnings",
+        "gauge",
+        "Sockets and processes the scan behind this scrape could not see, by kind",
+    );
+    let mut warnings: BTreeMap<&str, usize> = WARNING_KINDS.iter().map(|&k| (k, 0)).collect();
+    for warning in &scrape.warnings {
+        *warnings.entry(warning_kind(warning)).or_default() += 1;
+    }
+    for (kind, count) in warnings {
+        let _ = writeln!(out, "port_checker_scan_warnings{{kind=\"{kind}\"}} {count}");
+    }
+
+    header(
+        &mut out,
+        "port_checker_scan_errors_total",
+        "counter",
+        "Number of failed scans since the exporter started",
+    );
+    let _ = writeln!(out, "port_checker_scan_errors_total {scan_errors}");
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escape a Prometheus label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `request` to `handle_connection` and return its result with
+    /// the response
+    fn request(request: Vec<u8>) -> (io::Result<()>, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        client.write_all(&request).unwrap();
+        let result = handle_connection(
+            stream,
+            &mut Scanner::new(),
+            &ScanOptions::default(),
+            &[],
+            &mut 0,
+        );
+        let mut response = String::new();
+        let _ = client.read_to_string(&mut response);
+        (result, response)
+    }
+
+    #[test]
+    fn test_handle_connection() {
+        let (result, response) = request(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n".to_vec());
+        result.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+
+        // Headers past the cap are not read to the end
+        let mut long = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        long.resize(MAX_HEADER_BYTES as usize + 100, b'a');
+        let (result, response) = request(long);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(response.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut port = PortInfo::new(5432, "127.0.0.1".parse().unwrap(), 42, "post\"gres");
+        port.uid = Some(114);
+        port.user = Some("postgres".to_string());
+        let scrape = Scrape {
+            ports: vec![port],
+            warnings: vec![
+                ScanWarning::RestrictedProcess { pid: 7 },
+                ScanWarning::RestrictedProcess { pid: 9 },
+            ],
+            duration: Duration::from_millis(5),
+            ok: true,
+        };
+
+        let text = render(&scrape, &[5432, 6379], 2);
+        assert!(text.contains(
+            "port_checker_listener{port=\"5432\",address=\"127.0.0.1\",process=\"post\\\"gres\",user=\"postgres\"} 1"
+        ));
+        assert!(text.contains(
+            "port_checker_listener_info{port=\"5432\",address=\"127.0.0.1\",pid=\"42\"} 1"
+        ));
+        assert!(text.contains("port_checker_scan_warnings{kind=\"restricted_process\"} 2"));
+        assert!(text.contains("port_checker_scan_warnings{kind=\"hidden_socket\"} 0"));
+        assert!(text.contains("port_checker_expected_port_up{port=\"5432\"} 1"));
+        assert!(text.contains("port_checker_expected_port_up{port=\"6379\"} 0"));
+        assert!(text.contains("port_checker_scan_errors_total 2"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Listening TCP sockets in network namespaces other than our own, such as
+//! those of containers or `ip netns`.
+//!
+//! `/proc/<pid>/net/tcp` shows the sockets of the namespace `<pid>` is in,
+//! so one readable process per namespace is enough, without entering it.
+
+use crate::ports::Namespace;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::fs::MetadataExt;
+
+/// `TCP_LISTEN` as printed in the `st` column of `/proc/net/tcp`
+const TCP_LISTEN: &str = "0A";
+
+/// Where `ip netns add` mounts named namespaces
+const NAMED_NETNS_DIR: &str = "/run/netns";
+
+/// A network namespace and a process inside it whose `/proc/<pid>/net` can
+/// be read
+#[derive(Clone, Debug)]
+pub(crate) struct Foreign {
+    pub namespace: Namespace,
+    pub pid: u32,
+}
+
+/// A listening socket of a foreign namespace, not yet tied to a process
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Listener {
+    pub port: u16,
+    pub address: IpAddr,
+    pub inode: u32,
+}
+
+/// Every network namespace except our own that some visible process is in
+pub(crate) fn foreign_namespaces() -> Vec<Foreign> {
+    let own = namespace_inode("self");
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+
+    // Lowest PID per namespace, which is usually the container's init
+    let mut members: BTreeMap<u64, u32> = BTreeMap::new();
+    for pid in entries.filter_map(|e| e.ok()?.file_name().to_str()?.parse::<u32>().ok()) {
+        if let Some(inode) = namespace_inode(&pid.to_string()).filter(|&i| Some(i) != own) {
+            members
+                .entry(inode)
+                .and_modify(|first| *first = (*first).min(pid))
+                .or_insert(pid);
+        }
+    }
+
+    let named = named_namespaces();
+    members
+        .into_iter()
+        .map(|(inode, pid)| Foreign {
+            namespace: Namespace {
+                inode,
+                name: named
+                    .get(&inode)
+                    .map(|name| format!("netns:{name}"))
+                    .or_else(|| container_name(pid))
+                    .unwrap_or_else(|| format!("net:[{inode}]")),
+            },
+            pid,
+        })
+        .collect()
+}
+
+/// Inode of the network namespace of `/proc/<pid>`
+fn namespace_inode(pid: &str) -> Option<u64> {
+    let link = fs::read_link(format!("/proc/{pid}/ns/net")).ok()?;
+    link.to_str()?
+        .strip_prefix("net:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// Names given with `ip netns add`, by namespace inode
+fn named_namespaces() -> BTreeMap<u64, String> {
+    let Ok(entries) = fs::read_dir(NAMED_NETNS_DIR) else {
+        return BTreeMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let inode = fs::metadata(e.path()).ok()?.ino();
+            Some((inode, e.file_name().to_string_lossy().to_string()))
+        })
+        .collect()
+}
+
+/// `docker:<id>` style name of the container `pid` runs in, from its cgroup
+fn container_name(pid: u32) -> Option<String> {
+    let cgroup = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    cgroup
+        .lines()
+        .find_map(|line| parse_cgroup(line.rsplit(':').next()?))
+}
+
+/// Container runtime and short ID in a cgroup path, e.g.
+/// `/system.slice/docker-<id>.scope` or `/docker/<id>`
+fn parse_cgroup(path: &str) -> Option<String> {
+    const RUNTIMES: [(&str, &str); 5] = [
+        ("docker-", "docker"),
+        ("docker/", "docker"),
+        ("libpod-", "podman"),
+        ("cri-containerd-", "containerd"),
+        ("crio-", "cri-o"),
+    ];
+    for (marker, runtime) in RUNTIMES {
+        let Some((_, rest)) = path.split_once(marker) else {
+            continue;
+        };
+        let id: String = rest
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .take(12)
+            .collect();
+        if id.len() == 12 {
+            return Some(format!("{runtime}:{id}"));
+        }
+    }
+    None
+}
+
+/// Listening TCP sockets of the namespace `pid` is in
+pub(crate) fn listening_tcp(pid: u32, ipv4: bool, ipv6: bool) -> io::Result<Vec<Listener>> {
+    let mut listeners = Vec::new();
+    for (enabled, file) in [(ipv4, "tcp"), (ipv6, "tcp6")] {
+        if !enabled {
+            continue;
+        }
+        match fs::read_to_string(format!("/proc/{pid}/net/{file}")) {
+            Ok(table) => listeners.extend(table.lines().skip(1).filter_map(parse_line)),
+            // IPv6 may be disabled in the namespace
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(listeners)
+}
+
+/// Parse a listening socket from a `/proc/net/tcp` or `tcp6` line
+fn parse_line(line: &str) -> Option<Listener> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.get(3) != Some(&TCP_LISTEN) {
+        return None;
+    }
+    let (address, port) = fields.get(1)?.split_once(':')?;
+    Some(Listener {
+        port: u16::from_str_radix(port, 16).ok()?,
+        address: parse_address(address)?,
+        inode: fields.get(9)?.parse().ok()?,
+    })
+}
+
+/// Addresses are printed as 32-bit words in host byte order
+fn parse_address(hex: &str) -> Option<IpAddr> {
+    let mut octets = Vec::with_capacity(16);
+    for i in (0..hex.len()).step_by(8) {
+        let word = u32::from_str_radix(hex.get(i..i + 8)?, 16).ok()?;
+        octets.extend_from_slice(&word.to_ne_bytes());
+    }
+    match octets.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            octets[0], octets[1], octets[2], octets[3],
+        ))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(
+            <[u8; 16]>::try_from(octets).ok()?,
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let word = u32::from_ne_bytes([127, 0, 0, 1]);
+        let line = format!(
+            "   0: {word:08X}:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 54321 1 0000000000000000 100 0 0 10 0"
+        );
+        assert_eq!(
+            parse_line(&line),
+            Some(Listener {
+                port: 8080,
+                address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                inode: 54321,
+            })
+        );
+        // Established connections are skipped
+        assert_eq!(parse_line(&line.replace(" 0A ", " 01 ")), None);
+
+        let words: String = Ipv6Addr::LOCALHOST
+            .octets()
+            .chunks(4)
+            .map(|c| format!("{:08X}", u32::from_ne_bytes([c[0], c[1], c[2], c[3]])))
+            .collect();
+        assert_eq!(parse_address(&words), Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn test_parse_cgroup() {
+        let id = "4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a4f1c2a9e8b7d6c5a";
+        assert_eq!(
+            parse_cgroup(&format!("/system.slice/docker-{id}.scope")),
+            Some("docker:4f1c2a9e8b7d".to_string())
+        );
+        assert_eq!(
+            parse_cgroup(&format!("/docker/{id}")),
+            Some("docker:4f1c2a9e8b7d".to_string())
+        );
+        assert_eq!(
+            parse_cgroup(&format!(
+                "/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{id}.scope/container"
+            )),
+            Some("podman:4f1c2a9e8b7d".to_string())
+        );
+        assert_eq!(
+            parse_cgroup("/user.slice/user-1000.slice/session-2.scope"),
+            None
+        );
+    }
+}
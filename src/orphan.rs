@@ -0,0 +1,112 @@
+//! Dev servers left running after their terminal was closed.
+//!
+//! Closing a terminal normally ends what was started in it, but servers
+//! started with `&` or `nohup`, or that ignore SIGHUP, are reparented to
+//! init or to a subreaper such as `systemd --user` and keep their ports.
+//! They have no controlling terminal left.
+
+use port_checker::ports::PortInfo;
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+/// What `/proc/<pid>/stat` tells about a process's origin
+#[derive(Debug, PartialEq, Eq)]
+struct Stat {
+    ppid: u32,
+    /// Whether the process has a controlling terminal
+    tty: bool,
+}
+
+/// Whether each port's process lost its parent and terminal, in order
+pub fn detached(ports: &[PortInfo]) -> Vec<bool> {
+    let mut by_pid: HashMap<u32, bool> = HashMap::new();
+    ports
+        .iter()
+        .map(|p| *by_pid.entry(p.pid).or_insert_with(|| is_detached(p.pid)))
+        .collect()
+}
+
+fn is_detached(pid: u32) -> bool {
+    if pid == 1 {
+        return false;
+    }
+    match fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .as_deref()
+        .and_then(parse_stat)
+    {
+        Some(stat) => !stat.tty && (stat.ppid == 1 || is_user_subreaper(stat.ppid, pid)),
+        None => false,
+    }
+}
+
+/// Whether `pid` is the per-user service manager of `child`, which adopts
+/// the orphans of its user's sessions instead of init.
+///
+/// This is a heuristic: a `systemd --user` process owned by the same user
+/// as the child. It leaves out the system manager of a container, which is
+/// the normal parent of the container's services, and other users'
+/// managers.
+fn is_user_subreaper(pid: u32, child: u32) -> bool {
+    let is_systemd = fs::read_to_string(format!("/proc/{pid}/comm"))
+        .is_ok_and(|comm| comm.trim_end() == "systemd");
+    let is_user_manager = fs::read(format!("/proc/{pid}/cmdline"))
+        .is_ok_and(|cmdline| cmdline.split(|&b| b == 0).any(|arg| arg == b"--user"));
+    is_systemd && is_user_manager && owner(pid).is_some_and(|uid| Some(uid) == owner(child))
+}
+
+/// UID owning a process, from its `/proc` entry
+fn owner(pid: u32) -> Option<u32> {
+    fs::metadata(format!("/proc/{pid}")).ok().map(|m| m.uid())
+}
+
+/// Parse `/proc/<pid>/stat`. The command name is in parentheses and may
+/// itself contain spaces and parentheses, so fields are counted from the
+/// last `)`.
+fn parse_stat(stat: &str) -> Option<Stat> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // state ppid pgrp session tty_nr ...
+    let fields: Vec<&str> = rest.split_whitespace().take(5).collect();
+    Some(Stat {
+        ppid: fields.get(1)?.parse().ok()?,
+        tty: fields.get(4)?.parse::<i32>().ok()? != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (node) S 1 4200 4100 0 -1 4194560 1200 0 0 0 12 3 0 0 20 0 11 0";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                ppid: 1,
+                tty: false
+            })
+        );
+
+        let stat = "4300 (tmux: server) (x) S 900 4300 4300 34817 4300 4194304 0";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                ppid: 900,
+                tty: true
+            })
+        );
+        assert_eq!(parse_stat("4300 (node"), None);
+    }
+
+    #[test]
+    fn test_is_user_subreaper() {
+        let pid = std::process::id();
+        assert_eq!(owner(pid), Some(nix::unistd::getuid().as_raw()));
+        assert_eq!(owner(u32::MAX), None);
+        // This test binary is not a service manager
+        assert!(!is_user_subreaper(pid, pid));
+        assert!(!is_detached(pid));
+    }
+}
@@ -1,64 +1,734 @@
-use netstat2::{get_sockets_info, AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState};
-use sysinfo::{Pid, ProcessRefreshKind, System};
+use netstat2::{
+    iterate_sockets_info, AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::str::FromStr;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
 
-#[derive(Clone, Debug)]
+/// Identifies a listener across scans: port, owning PID and local address
+pub type ListenerKey = (u16, u32, IpAddr);
+
+/// A listening socket and the process that owns it.
+///
+/// New fields may be added in minor releases, so build this with
+/// [`PortInfo::new`] and set the fields you need.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct PortInfo {
     pub port: u16,
+    /// Local address the socket is bound to, e.g. 0.0.0.0 or ::1
+    #[serde(default = "unspecified_address")]
+    pub address: IpAddr,
     pub pid: u32,
     pub process_name: String,
     pub exe_path: Option<PathBuf>,
     pub cwd: Option<PathBuf>,
     pub cmd_args: Vec<String>,
+    /// Kernel details of the socket, when the collector provides them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<SocketDetails>,
+    /// Network namespace of the socket when it is not the scanner's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<Namespace>,
+    /// Real UID of the owning process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Name of the user `uid` belongs to, when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
 }
 
-pub fn get_listening_ports() -> Result<Vec<PortInfo>, Box<dyn std::error::Error>> {
-    let af_flags = AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6;
-    let proto_flags = ProtocolFlags::TCP;
-    let sockets = get_sockets_info(af_flags, proto_flags)?;
+/// A network namespace other than the scanner's own
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Namespace {
+    /// Inode of the namespace, as in `/proc/<pid>/ns/net`
+    pub inode: u64,
+    /// `docker:<id>` or similar for containers, `netns:<name>` for
+    /// `ip netns` namespaces, otherwise `net:[<inode>]`
+    pub name: String,
+}
 
-    let mut sys = System::new();
-    sys.refresh_processes_specifics(
-        sysinfo::ProcessesToUpdate::All,
-        true,
-        ProcessRefreshKind::everything(),
-    );
+/// Socket details reported by the [`Collector::SockDiag`] collector
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketDetails {
+    pub inode: u32,
+    /// UID that created the socket
+    pub uid: u32,
+    /// Connections waiting to be accepted
+    pub accept_queue: u32,
+    /// Most connections that may wait to be accepted (the listen backlog)
+    pub backlog: u32,
+}
 
-    let mut ports = Vec::new();
+/// A listening Unix domain socket and the process that owns it.
+///
+/// New fields may be added in minor releases, so build this with
+/// [`UnixSocketInfo::new`] and set the fields you need.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct UnixSocketInfo {
+    /// Filesystem path, or `@name` for an abstract socket
+    pub path: String,
+    pub inode: u32,
+    pub pid: u32,
+    pub process_name: String,
+    pub exe_path: Option<PathBuf>,
+    pub cwd: Option<PathBuf>,
+    pub cmd_args: Vec<String>,
+    /// Real UID of the owning process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Name of the user `uid` belongs to, when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+impl PortInfo {
+    /// A listener with only its port, address and process known
+    pub fn new(port: u16, address: IpAddr, pid: u32, process_name: impl Into<String>) -> Self {
+        Self {
+            port,
+            address,
+            pid,
+            process_name: process_name.into(),
+            exe_path: None,
+            cwd: None,
+            cmd_args: Vec::new(),
+            socket: None,
+            namespace: None,
+            uid: None,
+            user: None,
+        }
+    }
+
+    /// Identity of this listener, for matching it across scans
+    pub fn key(&self) -> ListenerKey {
+        (self.port, self.pid, self.address)
+    }
+}
+
+impl UnixSocketInfo {
+    /// A Unix socket with only its path, inode and process known
+    pub fn new(
+        path: impl Into<String>,
+        inode: u32,
+        pid: u32,
+        process_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            inode,
+            pid,
+            process_name: process_name.into(),
+            exe_path: None,
+            cwd: None,
+            cmd_args: Vec::new(),
+            uid: None,
+            user: None,
+        }
+    }
+}
+
+/// Snapshots written before addresses were recorded deserialize as 0.0.0.0
+fn unspecified_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+}
+
+/// How a scan reads the socket table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Collector {
+    /// The `netstat2` crate, available on every supported platform
+    #[default]
+    Netstat2,
+    /// Linux NETLINK_SOCK_DIAG, asking the kernel for listening sockets only
+    /// and caching which process holds each socket between scans with the
+    /// same [`Scanner`]. Fills in [`PortInfo::socket`].
+    SockDiag,
+}
+
+impl Collector {
+    pub fn name(self) -> &'static str {
+        match self {
+            Collector::Netstat2 => "netstat2",
+            Collector::SockDiag => "sock-diag",
+        }
+    }
+}
+
+impl FromStr for Collector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "netstat2" => Ok(Collector::Netstat2),
+            "sock-diag" => Ok(Collector::SockDiag),
+            other => Err(format!(
+                "unknown collector '{other}', expected netstat2 or sock-diag"
+            )),
+        }
+    }
+}
+
+/// What a scan should look at.
+///
+/// New options may be added in minor releases, so build this from
+/// `ScanOptions::default()` and set the fields you need.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScanOptions {
+    /// Include sockets bound to IPv4 addresses
+    pub ipv4: bool,
+    /// Include sockets bound to IPv6 addresses
+    pub ipv6: bool,
+    pub collector: Collector,
+    /// Also list listening Unix domain sockets (Linux only, otherwise none
+    /// are found)
+    pub unix: bool,
+    /// Also scan the network namespaces of containers and `ip netns`
+    /// (Linux only). Only namespaces of processes whose `/proc` entries are
+    /// readable are found, so run as root to see other users' containers.
+    pub namespaces: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            ipv4: true,
+            ipv6: true,
+            collector: Collector::default(),
+            unix: false,
+            namespaces: false,
+        }
+    }
+}
+
+/// Why a scan could not produce any results
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ScanError {
+    /// Not allowed to read the socket table
+    PermissionDenied(String),
+    /// Reading the socket table failed for another reason
+    SocketTable(String),
+    /// The requested collector does not work on this platform
+    Unsupported(String),
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::PermissionDenied(e) => {
+                write!(
+                    f,
+                    "permission denied reading the socket table ({e}): run as root"
+                )
+            }
+            ScanError::SocketTable(e) => write!(f, "failed to read the socket table: {e}"),
+            ScanError::Unsupported(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl From<io::Error> for ScanError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            ScanError::PermissionDenied(e.to_string())
+        } else {
+            ScanError::SocketTable(e.to_string())
+        }
+    }
+}
+
+impl From<netstat2::error::Error> for ScanError {
+    fn from(e: netstat2::error::Error) -> Self {
+        match &e {
+            netstat2::error::Error::OsError(io) if io.kind() == io::ErrorKind::PermissionDenied => {
+                ScanError::PermissionDenied(io.to_string())
+            }
+            _ => ScanError::SocketTable(e.to_string()),
+        }
+    }
+}
+
+/// Something a scan could not see, reported alongside the ports it did find
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ScanWarning {
+    /// A listening socket with no visible owning process, usually because
+    /// the process belongs to another user
+    HiddenSocket { port: u16, address: IpAddr },
+    /// A listening Unix socket with no visible owning process
+    HiddenUnixSocket { path: String },
+    /// A process whose path and working directory could not be read
+    RestrictedProcess { pid: u32 },
+    /// An entry of the socket table that could not be decoded
+    SocketError(String),
+    /// Running containers could not be listed, so listeners are not
+    /// matched to them
+    Containers(String),
+}
+
+/// Result of a scan: the ports found, and what could not be seen
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub ports: Vec<PortInfo>,
+    /// Listening Unix sockets, sorted by path, when [`ScanOptions::unix`] is set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unix_sockets: Vec<UnixSocketInfo>,
+    pub warnings: Vec<ScanWarning>,
+}
+
+impl ScanReport {
+    /// Whether some sockets or process details were hidden from the scan
+    pub fn is_partial(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+/// Summarize warnings as one line per kind, e.g. "12 sockets hidden: run as root"
+pub fn summarize_warnings(warnings: &[ScanWarning]) -> Vec<String> {
+    let hint = if is_root() { "" } else { ": run as root" };
+    let mut hidden = 0;
+    let mut hidden_unix = 0;
+    let mut restricted = 0;
+    let mut errors = Vec::new();
+    let mut containers = None;
+    for warning in warnings {
+        match warning {
+            ScanWarning::HiddenSocket { .. } => hidden += 1,
+            ScanWarning::HiddenUnixSocket { .. } => hidden_unix += 1,
+            ScanWarning::RestrictedProcess { .. } => restricted += 1,
+            ScanWarning::SocketError(e) => errors.push(e.as_str()),
+            ScanWarning::Containers(e) => containers = Some(e.as_str()),
+        }
+    }
+
+    let mut lines = Vec::new();
+    if hidden > 0 {
+        lines.push(format!(
+            "{hidden} {} hidden{hint}",
+            plural(hidden, "socket")
+        ));
+    }
+    if hidden_unix > 0 {
+        lines.push(format!(
+            "{hidden_unix} unix {} hidden{hint}",
+            plural(hidden_unix, "socket")
+        ));
+    }
+    if restricted > 0 {
+        lines.push(format!(
+            "{restricted} {} without path or cwd{hint}",
+            plural(restricted, "process")
+        ));
+    }
+    if !errors.is_empty() {
+        lines.push(format!(
+            "{} socket table {} skipped: {}",
+            errors.len(),
+            plural(errors.len(), "entry"),
+            errors.join("; ")
+        ));
+    }
+    if let Some(e) = containers {
+        lines.push(format!("containers not listed: {e}"));
+    }
+    lines
+}
 
-    for si in sockets {
+fn plural(count: usize, word: &str) -> String {
+    match (count, word) {
+        (1, _) => word.to_string(),
+        (_, "process") => "processes".to_string(),
+        (_, "entry") => "entries".to_string(),
+        _ => format!("{word}s"),
+    }
+}
+
+fn is_root() -> bool {
+    nix::unistd::geteuid().is_root()
+}
+
+/// List listening TCP sockets on IPv4 and IPv6, sorted by port.
+///
+/// Sockets and process details the scan could not see are left out; use
+/// [`scan`] to find out what was hidden.
+pub fn get_listening_ports() -> Result<Vec<PortInfo>, ScanError> {
+    scan(&ScanOptions::default()).map(|report| report.ports)
+}
+
+/// List listening TCP sockets matching `options`, sorted by port, along with
+/// warnings about sockets and processes that could not be inspected.
+///
+/// Use a [`Scanner`] instead when scanning repeatedly.
+pub fn scan(options: &ScanOptions) -> Result<ScanReport, ScanError> {
+    Scanner::new().scan(options)
+}
+
+/// Scans repeatedly, reusing process details between scans.
+///
+/// Each scan refreshes only the processes that own listening sockets. Their
+/// user is looked up once and kept while the process lives; the executable
+/// path, command line and working directory are read again every time.
+pub struct Scanner {
+    sys: System,
+    #[cfg(target_os = "linux")]
+    owners: crate::sock_diag::InodeOwners,
+    /// Kept apart from `owners`, which forgets inodes missing from a lookup
+    #[cfg(target_os = "linux")]
+    unix_owners: crate::sock_diag::InodeOwners,
+    #[cfg(target_os = "linux")]
+    namespace_owners: crate::sock_diag::InodeOwners,
+    /// User names by UID, or `None` for UIDs without a passwd entry
+    user_names: HashMap<u32, Option<String>>,
+}
+
+/// What a scan knows about the process owning a socket
+struct ProcessDetails {
+    name: String,
+    exe: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    cmd: Vec<String>,
+    uid: Option<u32>,
+    user: Option<String>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Self {
+            sys: System::new(),
+            #[cfg(target_os = "linux")]
+            owners: Default::default(),
+            #[cfg(target_os = "linux")]
+            unix_owners: Default::default(),
+            #[cfg(target_os = "linux")]
+            namespace_owners: Default::default(),
+            user_names: HashMap::new(),
+        }
+    }
+
+    pub fn scan(&mut self, options: &ScanOptions) -> Result<ScanReport, ScanError> {
+        let (mut listeners, mut warnings) = match options.collector {
+            Collector::Netstat2 => netstat2_listeners(options)?,
+            Collector::SockDiag => self.sock_diag_listeners(options)?,
+        };
+        if options.namespaces {
+            self.namespace_listeners(options, &mut listeners, &mut warnings);
+        }
+        let unix_listeners = if options.unix {
+            self.unix_listeners(&mut warnings)?
+        } else {
+            vec![]
+        };
+
+        // Refresh only the socket owners; processes that stopped listening
+        // drop out of the cache
+        let mut pids: Vec<Pid> = listeners
+            .iter()
+            .map(|l| l.pid)
+            .chain(unix_listeners.iter().map(|&(_, _, pid)| pid))
+            .map(Pid::from_u32)
+            .collect();
+        pids.sort_unstable();
+        pids.dedup();
+        // A process that execs keeps its PID and start time, so the
+        // executable and command line are read again every time
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&pids),
+            true,
+            ProcessRefreshKind::nothing()
+                .with_exe(UpdateKind::Always)
+                .with_cmd(UpdateKind::Always)
+                .with_cwd(UpdateKind::Always)
+                .with_user(UpdateKind::OnlyIfNotSet),
+        );
+
+        let mut ports = Vec::new();
+        for Listener {
+            port,
+            address,
+            pid,
+            socket,
+            namespace,
+        } in listeners
+        {
+            let mut details = self.process_details(pid, &mut warnings);
+            // The socket's creator stands in for a process that vanished
+            if details.uid.is_none() {
+                if let Some(uid) = socket.as_ref().map(|s| s.uid) {
+                    details.user = self.user_name(uid);
+                    details.uid = Some(uid);
+                }
+            }
+            ports.push(PortInfo {
+                port,
+                address,
+                pid,
+                process_name: details.name,
+                exe_path: details.exe,
+                cwd: details.cwd,
+                cmd_args: details.cmd,
+                socket,
+                namespace,
+                uid: details.uid,
+                user: details.user,
+            });
+        }
+
+        ports.sort_by_key(|p| p.key());
+        ports.dedup_by_key(|p| p.key());
+
+        let mut unix_sockets = Vec::new();
+        for (path, inode, pid) in unix_listeners {
+            let details = self.process_details(pid, &mut warnings);
+            unix_sockets.push(UnixSocketInfo {
+                path,
+                inode,
+                pid,
+                process_name: details.name,
+                exe_path: details.exe,
+                cwd: details.cwd,
+                cmd_args: details.cmd,
+                uid: details.uid,
+                user: details.user,
+            });
+        }
+        unix_sockets.sort_by(|a, b| (&a.path, a.pid).cmp(&(&b.path, b.pid)));
+
+        Ok(ScanReport {
+            ports,
+            unix_sockets,
+            warnings,
+        })
+    }
+
+    /// Name, executable, cwd, command line and user of `pid` from the last
+    /// refresh, noting in `warnings` when the details are hidden
+    fn process_details(&mut self, pid: u32, warnings: &mut Vec<ScanWarning>) -> ProcessDetails {
+        let mut details = if let Some(proc) = self.sys.process(Pid::from_u32(pid)) {
+            ProcessDetails {
+                name: proc.name().to_string_lossy().to_string(),
+                exe: proc.exe().map(|p| p.to_path_buf()),
+                cwd: proc.cwd().map(|p| p.to_path_buf()),
+                cmd: proc
+                    .cmd()
+                    .iter()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .collect(),
+                uid: proc.user_id().map(|uid| **uid),
+                user: None,
+            }
+        } else {
+            ProcessDetails {
+                name: String::from("unknown"),
+                exe: None,
+                cwd: None,
+                cmd: vec![],
+                uid: None,
+                user: None,
+            }
+        };
+        details.user = details.uid.and_then(|uid| self.user_name(uid));
+
+        let restricted = ScanWarning::RestrictedProcess { pid };
+        if details.exe.is_none() && details.cwd.is_none() && !warnings.contains(&restricted) {
+            warnings.push(restricted);
+        }
+        details
+    }
+
+    /// Name of the user with `uid`, looked up once per scanner
+    fn user_name(&mut self, uid: u32) -> Option<String> {
+        self.user_names
+            .entry(uid)
+            .or_insert_with(|| {
+                nix::unistd::User::from_uid(uid.into())
+                    .ok()
+                    .flatten()
+                    .map(|u| u.name)
+            })
+            .clone()
+    }
+
+    /// Add the listeners of other network namespaces. A namespace that
+    /// cannot be read is skipped, as its processes are hidden anyway.
+    #[cfg(target_os = "linux")]
+    fn namespace_listeners(
+        &mut self,
+        options: &ScanOptions,
+        listeners: &mut Vec<Listener>,
+        warnings: &mut Vec<ScanWarning>,
+    ) {
+        let mut sockets = Vec::new();
+        for foreign in crate::netns::foreign_namespaces() {
+            if let Ok(found) = crate::netns::listening_tcp(foreign.pid, options.ipv4, options.ipv6)
+            {
+                sockets.extend(found.into_iter().map(|s| (s, foreign.namespace.clone())));
+            }
+        }
+        let inodes: Vec<u32> = sockets.iter().map(|(s, _)| s.inode).collect();
+        let owners = self.namespace_owners.resolve(&inodes);
+
+        for (socket, namespace) in sockets {
+            match owners.get(&socket.inode) {
+                Some(&pid) => listeners.push(Listener {
+                    port: socket.port,
+                    address: socket.address,
+                    pid,
+                    socket: None,
+                    namespace: Some(namespace),
+                }),
+                None => warnings.push(ScanWarning::HiddenSocket {
+                    port: socket.port,
+                    address: socket.address,
+                }),
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn namespace_listeners(
+        &mut self,
+        _options: &ScanOptions,
+        _listeners: &mut Vec<Listener>,
+        _warnings: &mut Vec<ScanWarning>,
+    ) {
+    }
+
+    /// Listening Unix sockets as (path, inode, owning PID)
+    #[cfg(target_os = "linux")]
+    fn unix_listeners(
+        &mut self,
+        warnings: &mut Vec<ScanWarning>,
+    ) -> Result<Vec<(String, u32, u32)>, ScanError> {
+        let sockets = crate::sock_diag::listening_unix()?;
+        let inodes: Vec<u32> = sockets.iter().map(|s| s.inode).collect();
+        let owners = self.unix_owners.resolve(&inodes);
+
+        let mut listeners = Vec::new();
+        for socket in sockets {
+            match owners.get(&socket.inode) {
+                Some(&pid) => listeners.push((socket.path, socket.inode, pid)),
+                None => warnings.push(ScanWarning::HiddenUnixSocket { path: socket.path }),
+            }
+        }
+        Ok(listeners)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn unix_listeners(
+        &mut self,
+        _warnings: &mut Vec<ScanWarning>,
+    ) -> Result<Vec<(String, u32, u32)>, ScanError> {
+        Ok(vec![])
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sock_diag_listeners(
+        &mut self,
+        options: &ScanOptions,
+    ) -> Result<(Vec<Listener>, Vec<ScanWarning>), ScanError> {
+        let sockets = crate::sock_diag::listening_tcp(options.ipv4, options.ipv6)?;
+        let inodes: Vec<u32> = sockets.iter().map(|s| s.details.inode).collect();
+        let owners = self.owners.resolve(&inodes);
+
+        let mut listeners = Vec::new();
+        let mut warnings = Vec::new();
+        for socket in sockets {
+            match owners.get(&socket.details.inode) {
+                Some(&pid) => listeners.push(Listener {
+                    port: socket.port,
+                    address: socket.address,
+                    pid,
+                    socket: Some(socket.details),
+                    namespace: None,
+                }),
+                None => warnings.push(ScanWarning::HiddenSocket {
+                    port: socket.port,
+                    address: socket.address,
+                }),
+            }
+        }
+        Ok((listeners, warnings))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn sock_diag_listeners(
+        &mut self,
+        _options: &ScanOptions,
+    ) -> Result<(Vec<Listener>, Vec<ScanWarning>), ScanError> {
+        Err(ScanError::Unsupported(
+            "the sock-diag collector is only available on Linux".to_string(),
+        ))
+    }
+}
+
+/// A listening socket and its owner, before process details are added
+struct Listener {
+    port: u16,
+    address: IpAddr,
+    pid: u32,
+    socket: Option<SocketDetails>,
+    namespace: Option<Namespace>,
+}
+
+fn netstat2_listeners(
+    options: &ScanOptions,
+) -> Result<(Vec<Listener>, Vec<ScanWarning>), ScanError> {
+    let mut af_flags = AddressFamilyFlags::empty();
+    if options.ipv4 {
+        af_flags |= AddressFamilyFlags::IPV4;
+    }
+    if options.ipv6 {
+        af_flags |= AddressFamilyFlags::IPV6;
+    }
+    let proto_flags = ProtocolFlags::TCP;
+
+    // Keep the listening sockets that decode, reporting the rest as warnings
+    let mut warnings = Vec::new();
+    let mut listeners = Vec::new();
+    for entry in iterate_sockets_info(af_flags, proto_flags)? {
+        let si = match entry {
+            Ok(si) => si,
+            Err(e) => {
+                warnings.push(ScanWarning::SocketError(e.to_string()));
+                continue;
+            }
+        };
         if let ProtocolSocketInfo::Tcp(tcp) = si.protocol_socket_info {
             if tcp.state == TcpState::Listen {
                 // Take only the first associated PID for each socket
-                if let Some(pid) = si.associated_pids.first() {
-                    let pid_usize = *pid as usize;
-                    let (name, exe, cwd, cmd) = if let Some(proc) = sys.process(Pid::from(pid_usize)) {
-                        (
-                            proc.name().to_string_lossy().to_string(),
-                            proc.exe().map(|p| p.to_path_buf()),
-                            proc.cwd().map(|p| p.to_path_buf()),
-                            proc.cmd().iter().map(|s| s.to_string_lossy().to_string()).collect(),
-                        )
-                    } else {
-                        (String::from("unknown"), None, None, vec![])
-                    };
-
-                    ports.push(PortInfo {
+                match si.associated_pids.first() {
+                    Some(&pid) => listeners.push(Listener {
                         port: tcp.local_port,
-                        pid: *pid,
-                        process_name: name,
-                        exe_path: exe,
-                        cwd,
-                        cmd_args: cmd,
-                    });
+                        address: tcp.local_addr,
+                        pid,
+                        socket: None,
+                        namespace: None,
+                    }),
+                    None => warnings.push(ScanWarning::HiddenSocket {
+                        port: tcp.local_port,
+                        address: tcp.local_addr,
+                    }),
                 }
             }
         }
     }
-
-    ports.sort_by_key(|p| p.port);
-    ports.dedup_by_key(|p| (p.port, p.pid));
-    Ok(ports)
+    Ok((listeners, warnings))
 }
 
 #[cfg(test)]
@@ -66,15 +736,25 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_listening_ports() {
-        let ports = get_listening_ports().expect("Should get ports");
-        println!("Found {} listening ports:", ports.len());
-        for p in &ports {
-            println!(
-                "  Port {} - PID {} - {} - cwd: {:?}",
-                p.port, p.pid, p.process_name, p.cwd
-            );
-        }
-        // Should at least run without error
+    fn test_summarize_warnings() {
+        let warnings = vec![
+            ScanWarning::HiddenSocket {
+                port: 22,
+                address: unspecified_address(),
+            },
+            ScanWarning::HiddenSocket {
+                port: 631,
+                address: unspecified_address(),
+            },
+            ScanWarning::RestrictedProcess { pid: 1 },
+        ];
+        let lines = summarize_warnings(&warnings);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("2 sockets hidden"), "{}", lines[0]);
+        assert!(
+            lines[1].starts_with("1 process without path or cwd"),
+            "{}",
+            lines[1]
+        );
     }
 }
@@ -0,0 +1,152 @@
+//! Ancestry and children of a listener's process, to tell what launched it.
+
+use std::collections::HashMap;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+/// Most children listed; the rest are counted
+const MAX_CHILDREN: usize = 12;
+
+/// Guards against a parent loop in a racy process table
+const MAX_DEPTH: usize = 64;
+
+/// One process of a tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcEntry {
+    pub pid: u32,
+    pub name: String,
+    /// Command line, or the name when it is hidden or empty (kernel threads)
+    pub cmd: String,
+}
+
+/// A process with its parents up to PID 1 and its direct children
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessTree {
+    /// Outermost first, ending with the parent of `process`
+    pub ancestors: Vec<ProcEntry>,
+    pub process: ProcEntry,
+    pub children: Vec<ProcEntry>,
+    /// Children left out of `children`
+    pub more_children: usize,
+}
+
+impl ProcessTree {
+    /// Every entry in display order, with its depth: ancestors, the
+    /// process, then its children one level deeper
+    pub fn entries(&self) -> Vec<(usize, &ProcEntry)> {
+        let depth = self.ancestors.len();
+        self.ancestors
+            .iter()
+            .enumerate()
+            .chain(std::iter::once((depth, &self.process)))
+            .chain(self.children.iter().map(|c| (depth + 1, c)))
+            .collect()
+    }
+
+    /// Position of `process` in `entries`
+    pub fn process_index(&self) -> usize {
+        self.ancestors.len()
+    }
+}
+
+/// A process as read from the process table: (pid, parent, name, cmd)
+type Row = (u32, Option<u32>, String, String);
+
+/// Tree of `pid` from the live process table, or `None` once it has exited
+pub fn load(pid: u32) -> Option<ProcessTree> {
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cmd(UpdateKind::Always),
+    );
+    let rows: Vec<Row> = sys
+        .processes()
+        .values()
+        // Threads share their process's PID on Linux and would show twice
+        .filter(|p| p.thread_kind().is_none())
+        .map(|p| {
+            let cmd: Vec<String> = p
+                .cmd()
+                .iter()
+                .map(|s| s.to_string_lossy().to_string())
+                .collect();
+            (
+                p.pid().as_u32(),
+                p.parent().map(Pid::as_u32),
+                p.name().to_string_lossy().to_string(),
+                cmd.join(" "),
+            )
+        })
+        .collect();
+    build(pid, &rows)
+}
+
+/// Tree of `pid` within `rows`
+fn build(pid: u32, rows: &[Row]) -> Option<ProcessTree> {
+    let by_pid: HashMap<u32, &Row> = rows.iter().map(|r| (r.0, r)).collect();
+    let entry = |row: &Row| ProcEntry {
+        pid: row.0,
+        name: row.2.clone(),
+        cmd: if row.3.is_empty() {
+            format!("[{}]", row.2)
+        } else {
+            row.3.clone()
+        },
+    };
+
+    let process = *by_pid.get(&pid)?;
+    let mut ancestors = Vec::new();
+    let mut parent = process.1;
+    while let Some(row) = parent.and_then(|ppid| by_pid.get(&ppid)) {
+        if ancestors.len() == MAX_DEPTH {
+            break;
+        }
+        ancestors.push(entry(row));
+        parent = row.1.filter(|&ppid| ppid != row.0);
+    }
+    ancestors.reverse();
+
+    let mut children: Vec<&Row> = rows.iter().filter(|r| r.1 == Some(pid)).collect();
+    children.sort_by_key(|r| r.0);
+    let more_children = children.len().saturating_sub(MAX_CHILDREN);
+    Some(ProcessTree {
+        ancestors,
+        process: entry(process),
+        children: children.into_iter().take(MAX_CHILDREN).map(entry).collect(),
+        more_children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pid: u32, parent: Option<u32>, name: &str, cmd: &str) -> Row {
+        (pid, parent, name.to_string(), cmd.to_string())
+    }
+
+    #[test]
+    fn test_build() {
+        let rows = vec![
+            row(1, None, "systemd", "/sbin/init"),
+            row(2, None, "kthreadd", ""),
+            row(900, Some(1), "tmux: server", "tmux new -s dev"),
+            row(4100, Some(900), "bash", "-bash"),
+            row(4200, Some(4100), "npm run dev", "npm run dev"),
+            row(4242, Some(4200), "node", "node next dev"),
+            row(4301, Some(4242), "esbuild", "esbuild --service"),
+            row(4300, Some(4242), "node", "node worker.js"),
+        ];
+        let tree = build(4242, &rows).unwrap();
+        let pids: Vec<u32> = tree.ancestors.iter().map(|a| a.pid).collect();
+        assert_eq!(pids, [1, 900, 4100, 4200]);
+        assert_eq!(tree.process.cmd, "node next dev");
+        let children: Vec<u32> = tree.children.iter().map(|c| c.pid).collect();
+        assert_eq!(children, [4300, 4301]);
+        assert_eq!(tree.process_index(), 4);
+        assert_eq!(tree.entries()[5], (5, &tree.children[0]));
+
+        assert_eq!(build(2, &rows).unwrap().process.cmd, "[kthreadd]");
+        assert_eq!(build(7, &rows), None);
+    }
+}
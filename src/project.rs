@@ -0,0 +1,137 @@
+//! Projects that listeners run from.
+//!
+//! A listener's project is found by walking up from its working directory
+//! to the root of a git checkout, or failing that to the nearest directory
+//! with a package manifest.
+
+use port_checker::ports::PortInfo;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files that mark the root of a project outside any git checkout
+const MANIFESTS: [&str; 4] = ["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Project {
+    /// Name of the root directory
+    pub name: String,
+    pub root: PathBuf,
+    /// Checked-out branch, or the short commit of a detached HEAD; `None`
+    /// outside git
+    pub branch: Option<String>,
+}
+
+impl Project {
+    fn at(root: &Path, branch: Option<String>) -> Self {
+        Self {
+            name: root.file_name().map_or_else(
+                || root.to_string_lossy().to_string(),
+                |name| name.to_string_lossy().to_string(),
+            ),
+            root: root.to_path_buf(),
+            branch,
+        }
+    }
+}
+
+/// Project of each port in `ports`, in order
+pub fn projects(ports: &[PortInfo]) -> Vec<Option<Project>> {
+    let mut by_cwd: HashMap<&Path, Option<Project>> = HashMap::new();
+    ports
+        .iter()
+        .map(|p| {
+            let cwd = p.cwd.as_deref()?;
+            by_cwd.entry(cwd).or_insert_with(|| detect(cwd)).clone()
+        })
+        .collect()
+}
+
+/// Project containing `cwd`: the enclosing git checkout, else the nearest
+/// directory with a manifest
+pub fn detect(cwd: &Path) -> Option<Project> {
+    let mut manifest = None;
+    for dir in cwd.ancestors() {
+        let git = dir.join(".git");
+        if git.exists() {
+            return Some(Project::at(dir, branch(dir, &git)));
+        }
+        if manifest.is_none() && MANIFESTS.iter().any(|m| dir.join(m).is_file()) {
+            manifest = Some(dir);
+        }
+    }
+    manifest.map(|dir| Project::at(dir, None))
+}
+
+/// Branch checked out in the repository whose `.git` is `git`. Worktrees
+/// and submodules have a `.git` file pointing at the real git directory.
+fn branch(dir: &Path, git: &Path) -> Option<String> {
+    let git_dir = if git.is_file() {
+        let link = fs::read_to_string(git).ok()?;
+        dir.join(link.strip_prefix("gitdir:")?.trim())
+    } else {
+        git.to_path_buf()
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    parse_head(&head)
+}
+
+/// Branch named by the contents of a `HEAD` file
+fn parse_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            )
+        }
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let base =
+            std::env::temp_dir().join(format!("port-checker-project-{}", std::process::id()));
+        let repo = base.join("shop");
+        let server = repo.join("services/api/src");
+        fs::create_dir_all(&server).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/feature/cart\n").unwrap();
+        // A manifest inside a checkout does not split the repository up
+        fs::write(repo.join("services/api/package.json"), "{}").unwrap();
+        let tool = base.join("tool/bin");
+        fs::create_dir_all(&tool).unwrap();
+        fs::write(base.join("tool/Cargo.toml"), "").unwrap();
+
+        let project = detect(&server).unwrap();
+        assert_eq!(project.name, "shop");
+        assert_eq!(project.root, repo);
+        assert_eq!(project.branch.as_deref(), Some("feature/cart"));
+
+        let project = detect(&tool).unwrap();
+        assert_eq!((project.name.as_str(), project.branch), ("tool", None));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_parse_head() {
+        assert_eq!(
+            parse_head("ref: refs/heads/main\n").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            parse_head("3f9a2c1d0e8b7a6f5e4d3c2b1a0f9e8d7c6b5a49\n").as_deref(),
+            Some("3f9a2c1")
+        );
+    }
+}
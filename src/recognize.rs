@@ -0,0 +1,318 @@
+//! Friendly labels for common dev tools, read from their command lines.
+//!
+//! Interpreters such as `node`, `python3` and `java` say little about what
+//! they run. Each recognizer looks at a command line and names the script,
+//! module, main class or target it runs. To support another tool, add a
+//! function to `RECOGNIZERS`; the first one that matches wins.
+
+use std::path::Path;
+
+/// Label for a command line, or `None` when it is not the recognizer's tool
+type Recognizer = fn(&[&str]) -> Option<String>;
+
+/// Tried in order, so more specific tools come first
+const RECOGNIZERS: &[Recognizer] = &[
+    kubectl_port_forward,
+    cargo_run,
+    package_script,
+    node_tool,
+    rails,
+    puma,
+    python,
+    java,
+];
+
+/// What `cmd_args` runs, when some recognizer knows it
+pub fn label(cmd_args: &[String]) -> Option<String> {
+    let mut args: Vec<&str> = cmd_args.iter().map(String::as_str).collect();
+    // Servers that set their process title show up as one argument
+    if let [title] = args[..] {
+        args = title.split_whitespace().collect();
+    }
+    RECOGNIZERS.iter().find_map(|recognize| recognize(&args))
+}
+
+/// File name of a path argument
+fn base(arg: &str) -> &str {
+    Path::new(arg)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(arg)
+}
+
+/// Arguments after the program, skipping interpreters and launchers such
+/// as `node`, `ruby` and `bundle exec` that run the program named next
+fn program<'a>(args: &'a [&'a str]) -> (&'a str, &'a [&'a str]) {
+    let mut rest = args;
+    loop {
+        match rest {
+            [first, tail @ ..] if matches!(base(first), "node" | "ruby" | "bun" | "deno") => {
+                // Skip interpreter options such as --inspect
+                let skip = tail.iter().take_while(|a| a.starts_with('-')).count();
+                rest = &tail[skip..];
+            }
+            [first, "exec", tail @ ..] if base(first) == "bundle" => rest = tail,
+            [first, tail @ ..] => return (base(first), tail),
+            [] => return ("", &[]),
+        }
+    }
+}
+
+/// First argument that is not an option
+fn positional<'a>(args: &[&'a str]) -> Option<&'a str> {
+    args.iter().copied().find(|a| !a.starts_with('-'))
+}
+
+/// First argument when it is a subcommand or target rather than an option
+/// or a path
+fn subcommand<'a>(args: &[&'a str]) -> Option<&'a str> {
+    args.first()
+        .copied()
+        .filter(|a| !a.starts_with('-') && !a.contains('/'))
+}
+
+/// Value of `--name value` or `--name=value`, for any of `names`
+fn option<'a>(args: &[&'a str], names: &[&str]) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        names.iter().find_map(|name| {
+            if arg == name {
+                args.get(i + 1).copied()
+            } else {
+                arg.strip_prefix(name)?.strip_prefix('=')
+            }
+        })
+    })
+}
+
+/// `npm run dev`, `yarn start`, `pnpm dev`: the package.json script
+fn package_script(args: &[&str]) -> Option<String> {
+    let (tool, rest) = program(args);
+    let tool = tool.strip_suffix("-cli.js").unwrap_or(tool);
+    if !matches!(tool, "npm" | "yarn" | "yarn.js" | "pnpm" | "bun") {
+        return None;
+    }
+    let tool = tool.trim_end_matches(".js");
+    match positional(rest)? {
+        "run" | "run-script" => Some(format!("{tool} run {}", positional(&rest[1..])?)),
+        script => Some(format!("{tool} {script}")),
+    }
+}
+
+/// Node dev servers: `next dev`, `vite`, `nuxt dev` and other packages'
+/// binaries, run from `node_modules/.bin` or the package directory
+fn node_tool(args: &[&str]) -> Option<String> {
+    let (program, rest) = program(args);
+    let tool = match program {
+        // Process titles set by the servers themselves
+        "next-server" | "next-router-worker" => return Some("next".to_string()),
+        "vite" | "next" | "nuxt" | "nuxi" | "astro" | "remix" | "webpack"
+        | "webpack-dev-server" | "react-scripts" | "ng" | "nodemon" | "tsx" | "ts-node" => program,
+        _ if base(args.first()?) == "node" => {
+            // node /app/node_modules/vite/bin/vite.js
+            let script = args.iter().find(|a| a.contains("node_modules/"))?;
+            let package = script.rsplit_once("node_modules/")?.1;
+            let package = package.strip_prefix(".bin/").unwrap_or(package);
+            package.split('/').next().filter(|p| !p.is_empty())?
+        }
+        _ => return None,
+    };
+    Some(match subcommand(rest) {
+        Some(command) => format!("{tool} {command}"),
+        None => tool.to_string(),
+    })
+}
+
+/// `python -m module` or `python script.py`, naming what runs
+fn python(args: &[&str]) -> Option<String> {
+    let (first, rest) = args.split_first()?;
+    let interpreter = base(first);
+    let version = interpreter.strip_prefix("python")?;
+    if !version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let mut rest = rest;
+    while let [arg, tail @ ..] = rest {
+        match *arg {
+            "-m" => {
+                let (module, tail) = tail.split_first()?;
+                return Some(match subcommand(tail) {
+                    // uvicorn app:app, flask run
+                    Some(target) => format!("{module} {target}"),
+                    None => module.to_string(),
+                });
+            }
+            "-c" => return None,
+            // Options whose value is the next argument
+            "-W" | "-X" => rest = tail.get(1..)?,
+            arg if arg.starts_with('-') => rest = tail,
+            script => {
+                let script = base(script);
+                return Some(match subcommand(tail) {
+                    // manage.py runserver
+                    Some(command) if script == "manage.py" => format!("{script} {command}"),
+                    _ => script.to_string(),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// `java -jar app.jar` or the main class of `java [options] com.example.Main`
+fn java(args: &[&str]) -> Option<String> {
+    let (first, rest) = args.split_first()?;
+    if base(first) != "java" {
+        return None;
+    }
+    let mut rest = rest;
+    while let [arg, tail @ ..] = rest {
+        match *arg {
+            "-jar" => return tail.first().map(|jar| base(jar).to_string()),
+            "-m" | "--module" => return tail.first().map(|module| module.to_string()),
+            // Options whose value is the next argument
+            "-cp" | "-classpath" | "--class-path" | "-p" | "--module-path" | "--add-opens"
+            | "--add-exports" | "--add-modules" => rest = tail.get(1..)?,
+            arg if arg.starts_with('-') => rest = tail,
+            class => return Some(class.to_string()),
+        }
+    }
+    None
+}
+
+/// `cargo run` with its binary, example or package
+fn cargo_run(args: &[&str]) -> Option<String> {
+    let (first, rest) = args.split_first()?;
+    if base(first) != "cargo" || positional(rest) != Some("run") {
+        return None;
+    }
+    let target = option(rest, &["--bin", "--example", "--package", "-p"]);
+    Some(match target {
+        Some(target) => format!("cargo run {target}"),
+        None => "cargo run".to_string(),
+    })
+}
+
+/// `rails server` and `rails s`, however rails is launched
+fn rails(args: &[&str]) -> Option<String> {
+    let (program, rest) = program(args);
+    if program != "rails" {
+        return None;
+    }
+    match positional(rest)? {
+        "s" | "server" => Some("rails server".to_string()),
+        command => Some(format!("rails {command}")),
+    }
+}
+
+/// Puma's process title, `puma 6.4.0 (tcp://0.0.0.0:3000) [app]`, or its
+/// command line
+fn puma(args: &[&str]) -> Option<String> {
+    let (program, _) = program(args);
+    // Cluster workers are titled `puma: cluster worker 0: ...`
+    if program.trim_end_matches(':') != "puma" {
+        return None;
+    }
+    let app = args
+        .iter()
+        .rev()
+        .find_map(|a| a.strip_prefix('[')?.strip_suffix(']'));
+    Some(match app {
+        Some(app) => format!("puma {app}"),
+        None => "puma".to_string(),
+    })
+}
+
+/// `kubectl port-forward` with its resource, ports and namespace
+fn kubectl_port_forward(args: &[&str]) -> Option<String> {
+    let (first, rest) = args.split_first()?;
+    let position = rest.iter().position(|a| *a == "port-forward")?;
+    if base(first) != "kubectl" {
+        return None;
+    }
+    let mut targets = Vec::new();
+    let mut rest = &rest[position + 1..];
+    while let [arg, tail @ ..] = rest {
+        rest = tail;
+        match *arg {
+            "-n" | "--namespace" | "--address" | "--pod-running-timeout" | "--context" => {
+                rest = tail.get(1..).unwrap_or_default();
+            }
+            arg if arg.starts_with('-') => {}
+            target => targets.push(target),
+        }
+    }
+    let namespace = option(args, &["-n", "--namespace"]);
+    let mut label = format!("kubectl port-forward {}", targets.join(" "));
+    if let Some(namespace) = namespace {
+        label.push_str(&format!(" -n {namespace}"));
+    }
+    Some(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(cmd: &str, expected: Option<&str>) {
+        let args: Vec<String> = cmd.split(' ').map(str::to_string).collect();
+        assert_eq!(label(&args).as_deref(), expected, "{cmd}");
+    }
+
+    #[test]
+    fn test_label() {
+        check(
+            "node /usr/lib/node_modules/npm/bin/npm-cli.js run dev",
+            Some("npm run dev"),
+        );
+        check("npm start", Some("npm start"));
+        check("pnpm storybook", Some("pnpm storybook"));
+        check(
+            "node /srv/web/node_modules/.bin/vite --port 5173",
+            Some("vite"),
+        );
+        check("node /srv/web/node_modules/.bin/next dev", Some("next dev"));
+        check("node /srv/web/node_modules/vite/bin/vite.js", Some("vite"));
+        check("next-server (v14.2.3)", Some("next"));
+        check("node --inspect server.js", None);
+        check(
+            "python3 -m http.server --bind 127.0.0.1",
+            Some("http.server"),
+        );
+        check(
+            "python3.12 -m uvicorn app.main:app --reload",
+            Some("uvicorn app.main:app"),
+        );
+        check(
+            "python manage.py runserver 0.0.0.0:8000",
+            Some("manage.py runserver"),
+        );
+        check("/usr/bin/python3 -u /srv/api/serve.py", Some("serve.py"));
+        check(
+            "java -Xmx1g -jar /opt/app/build/libs/shop-0.1.jar",
+            Some("shop-0.1.jar"),
+        );
+        check(
+            "java -cp lib/* -Dx=y com.example.Main --port 8080",
+            Some("com.example.Main"),
+        );
+        check("cargo run --release --bin api", Some("cargo run api"));
+        check("cargo run", Some("cargo run"));
+        check("cargo build", None);
+        check("ruby bin/rails s -p 3000", Some("rails server"));
+        check("bundle exec rails server", Some("rails server"));
+        let title = ["puma 6.4.0 (tcp://0.0.0.0:3000) [shop]".to_string()];
+        assert_eq!(label(&title).as_deref(), Some("puma shop"));
+        check(
+            "kubectl port-forward -n shop svc/web 8080:80",
+            Some("kubectl port-forward svc/web 8080:80 -n shop"),
+        );
+        check(
+            "sshd: /usr/sbin/sshd -D [listener] 0 of 10-100 startups",
+            None,
+        );
+        check(
+            "/usr/lib/postgresql/16/bin/postgres -D /var/lib/postgresql/16/main",
+            None,
+        );
+    }
+}
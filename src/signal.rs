@@ -0,0 +1,43 @@
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::io;
+
+/// Ask a process to exit with SIGTERM
+pub fn terminate(pid: u32) -> io::Result<()> {
+    send_signal(pid, false)
+}
+
+/// Kill a process immediately with SIGKILL
+pub fn force_kill(pid: u32) -> io::Result<()> {
+    send_signal(pid, true)
+}
+
+/// Send SIGTERM, or SIGKILL when `force` is set, using proper signal handling via nix.
+///
+/// PID 0 and PIDs above `i32::MAX` are rejected with `InvalidInput`, since
+/// kill(2) would take them as the caller's process group or every process.
+pub fn send_signal(pid: u32, force: bool) -> io::Result<()> {
+    let signal = if force {
+        Signal::SIGKILL
+    } else {
+        Signal::SIGTERM
+    };
+    let pid = i32::try_from(pid)
+        .ok()
+        .filter(|&pid| pid > 0)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "PID out of range"))?;
+    kill(Pid::from_raw(pid), signal).map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_signal_rejects_group_pids() {
+        for pid in [0, u32::MAX, i32::MAX as u32 + 1] {
+            let err = send_signal(pid, false).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{pid}");
+        }
+    }
+}
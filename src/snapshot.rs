@@ -0,0 +1,322 @@
+use port_checker::ports::PortInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Snapshot file format version, bumped on incompatible changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// A saved list of listening ports, written to disk as JSON
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Seconds since the Unix epoch when the scan was taken
+    pub taken_at: u64,
+    pub hostname: Option<String>,
+    pub ports: Vec<PortInfo>,
+}
+
+impl Snapshot {
+    pub fn new(ports: Vec<PortInfo>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: unix_now(),
+            hostname: sysinfo::System::host_name(),
+            ports,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(path)?;
+        let snapshot: Snapshot = serde_json::from_str(&json)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "{} was written by a newer version (format {}, supported {})",
+                path.display(),
+                snapshot.version,
+                SNAPSHOT_VERSION
+            )
+            .into());
+        }
+        Ok(snapshot)
+    }
+
+    /// Short description used in titles, e.g. "host @ 2024-05-01 10:00:00 UTC"
+    pub fn label(&self) -> String {
+        match &self.hostname {
+            Some(host) => format!("{host} @ {}", format_timestamp(self.taken_at)),
+            None => format_timestamp(self.taken_at),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl DiffKind {
+    pub fn marker(self) -> &'static str {
+        match self {
+            DiffKind::Added => "+",
+            DiffKind::Removed => "-",
+            DiffKind::Changed => "~",
+            DiffKind::Unchanged => " ",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    pub old: Option<PortInfo>,
+    pub new: Option<PortInfo>,
+    /// Human-readable descriptions of what differs for `Changed` entries
+    pub changes: Vec<String>,
+}
+
+impl DiffEntry {
+    /// The side of the entry to show in tables: the new one unless it was removed
+    pub fn port_info(&self) -> &PortInfo {
+        self.new
+            .as_ref()
+            .or(self.old.as_ref())
+            .expect("diff entry has at least one side")
+    }
+}
+
+/// Listeners from the old and new side sharing a port and process name
+type OldAndNew<'a> = (Vec<&'a PortInfo>, Vec<&'a PortInfo>);
+
+/// Compare two port lists.
+///
+/// Listeners are matched by port and process name, so a service that was
+/// restarted under a new PID is not reported as removed and re-added. PID
+/// changes alone do not make an entry `Changed`, but are listed in `changes`
+/// when some other field differs.
+pub fn diff(old: &[PortInfo], new: &[PortInfo]) -> Vec<DiffEntry> {
+    let mut groups: BTreeMap<(u16, &str), OldAndNew> = BTreeMap::new();
+    for p in old {
+        groups
+            .entry((p.port, &p.process_name))
+            .or_default()
+            .0
+            .push(p);
+    }
+    for p in new {
+        groups
+            .entry((p.port, &p.process_name))
+            .or_default()
+            .1
+            .push(p);
+    }
+
+    let mut entries = Vec::new();
+    for (mut olds, mut news) in groups.into_values() {
+        olds.sort_by_key(|p| p.key());
+        news.sort_by_key(|p| p.key());
+        let mut olds = olds.into_iter();
+        let mut news = news.into_iter();
+        loop {
+            match (olds.next(), news.next()) {
+                (Some(o), Some(n)) => {
+                    let changes = describe_changes(o, n);
+                    let kind = if changes.is_empty() {
+                        DiffKind::Unchanged
+                    } else {
+                        DiffKind::Changed
+                    };
+                    let changes = if kind == DiffKind::Changed && o.pid != n.pid {
+                        let mut all = vec![format!("pid: {} -> {}", o.pid, n.pid)];
+                        all.extend(changes);
+                        all
+                    } else {
+                        changes
+                    };
+                    entries.push(DiffEntry {
+                        kind,
+                        old: Some(o.clone()),
+                        new: Some(n.clone()),
+                        changes,
+                    });
+                }
+                (Some(o), None) => entries.push(DiffEntry {
+                    kind: DiffKind::Removed,
+                    old: Some(o.clone()),
+                    new: None,
+                    changes: vec![],
+                }),
+                (None, Some(n)) => entries.push(DiffEntry {
+                    kind: DiffKind::Added,
+                    old: None,
+                    new: Some(n.clone()),
+                    changes: vec![],
+                }),
+                (None, None) => break,
+            }
+        }
+    }
+    entries
+}
+
+fn describe_changes(old: &PortInfo, new: &PortInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.address != new.address {
+        changes.push(format!("address: {} -> {}", old.address, new.address));
+    }
+    if old.exe_path != new.exe_path {
+        changes.push(format!(
+            "path: {} -> {}",
+            display_path(old.exe_path.as_deref()),
+            display_path(new.exe_path.as_deref())
+        ));
+    }
+    if old.cwd != new.cwd {
+        changes.push(format!(
+            "cwd: {} -> {}",
+            display_path(old.cwd.as_deref()),
+            display_path(new.cwd.as_deref())
+        ));
+    }
+    if old.cmd_args != new.cmd_args {
+        changes.push(format!(
+            "command: {} -> {}",
+            old.cmd_args.join(" "),
+            new.cmd_args.join(" ")
+        ));
+    }
+    // Snapshots from before owners were recorded have no UID to compare
+    if let (Some(old_uid), Some(new_uid)) = (old.uid, new.uid) {
+        if old_uid != new_uid {
+            let name = |p: &PortInfo, uid: u32| p.user.clone().unwrap_or_else(|| uid.to_string());
+            changes.push(format!(
+                "user: {} -> {}",
+                name(old, old_uid),
+                name(new, new_uid)
+            ));
+        }
+    }
+    changes
+}
+
+fn display_path(path: Option<&Path>) -> String {
+    path.map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Render a diff as plain text lines for the CLI
+pub fn format_diff(entries: &[DiffEntry]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for entry in entries.iter().filter(|e| e.kind != DiffKind::Unchanged) {
+        let p = entry.port_info();
+        lines.push(format!(
+            "{} {:<6} {:<16} {:<20} PID {}",
+            entry.kind.marker(),
+            p.port,
+            p.address,
+            p.process_name,
+            p.pid
+        ));
+        for change in &entry.changes {
+            lines.push(format!("      {change}"));
+        }
+    }
+
+    let count = |kind| entries.iter().filter(|e| e.kind == kind).count();
+    lines.push(format!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        count(DiffKind::Added),
+        count(DiffKind::Removed),
+        count(DiffKind::Changed),
+        count(DiffKind::Unchanged)
+    ));
+    lines
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format seconds since the Unix epoch as "YYYY-MM-DD HH:MM:SS UTC"
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn port(port: u16, pid: u32, name: &str, cwd: &str) -> PortInfo {
+        let mut info = PortInfo::new(port, "0.0.0.0".parse().unwrap(), pid, name);
+        info.cwd = Some(PathBuf::from(cwd));
+        info
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            port(22, 10, "sshd", "/"),
+            port(3000, 20, "node", "/srv/a"),
+            port(5432, 30, "postgres", "/var/lib/pg"),
+        ];
+        let new = vec![
+            port(22, 11, "sshd", "/"),
+            port(3000, 21, "node", "/srv/b"),
+            port(8080, 40, "java", "/opt"),
+        ];
+
+        let kinds: Vec<(u16, DiffKind)> = diff(&old, &new)
+            .iter()
+            .map(|e| (e.port_info().port, e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (22, DiffKind::Unchanged),
+                (3000, DiffKind::Changed),
+                (5432, DiffKind::Removed),
+                (8080, DiffKind::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_709_210_096), "2024-02-29 12:34:56 UTC");
+    }
+}
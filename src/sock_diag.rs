@@ -0,0 +1,479 @@
+//! Listening TCP and Unix sockets straight from the kernel over
+//! NETLINK_SOCK_DIAG.
+//!
+//! Unlike the netstat2 path, the kernel filters the dump down to listening
+//! sockets, and socket inodes are mapped to PIDs through a `/proc/*/fd` scan
+//! that is cached between scans.
+
+use crate::ports::SocketDetails;
+use nix::libc;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// `SOCK_DIAG_BY_FAMILY` from linux/sock_diag.h
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+/// `TCP_LISTEN` from the kernel's TCP state enum
+const TCP_LISTEN: u32 = 10;
+
+const NLMSG_HEADER_LEN: usize = 16;
+
+/// Length of `struct inet_diag_req_v2`
+const REQUEST_LEN: usize = 56;
+
+/// Length of `struct inet_diag_msg`
+const DIAG_MSG_LEN: usize = 72;
+
+/// Length of `struct unix_diag_req`
+const UNIX_REQUEST_LEN: usize = 24;
+
+/// Length of `struct unix_diag_msg`, which attributes follow
+const UNIX_DIAG_MSG_LEN: usize = 16;
+
+/// `UDIAG_SHOW_NAME` from linux/unix_diag.h: include the bound name
+const UDIAG_SHOW_NAME: u32 = 1;
+
+/// `UNIX_DIAG_NAME` attribute type
+const UNIX_DIAG_NAME: u16 = 0;
+
+const RECV_BUFFER_LEN: usize = 32 * 1024;
+
+/// A listening socket as reported by the kernel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Listener {
+    pub port: u16,
+    pub address: IpAddr,
+    pub details: SocketDetails,
+}
+
+/// A listening Unix domain socket as reported by the kernel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct UnixListener {
+    /// Filesystem path, or `@name` for an abstract socket
+    pub path: String,
+    pub inode: u32,
+}
+
+/// Dump the listening TCP sockets of the requested address families
+pub(crate) fn listening_tcp(ipv4: bool, ipv6: bool) -> io::Result<Vec<Listener>> {
+    let mut listeners = Vec::new();
+    for (enabled, family) in [(ipv4, libc::AF_INET), (ipv6, libc::AF_INET6)] {
+        if enabled {
+            dump(&inet_request(family as u8), |msg| {
+                listeners.push(parse_diag_msg(msg)?);
+                Ok(())
+            })?;
+        }
+    }
+    Ok(listeners)
+}
+
+/// Dump the listening Unix domain sockets of every type
+pub(crate) fn listening_unix() -> io::Result<Vec<UnixListener>> {
+    let mut listeners = Vec::new();
+    dump(&unix_request(), |msg| {
+        listeners.push(parse_unix_diag_msg(msg)?);
+        Ok(())
+    })?;
+    Ok(listeners)
+}
+
+/// Send `request` and pass the payload of every reply to `on_message`
+fn dump(request: &[u8], mut on_message: impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+    // SAFETY: plain socket(2) call; the result is checked before use
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_SOCK_DIAG,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: fd is a freshly created socket that nothing else owns
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: sockaddr_nl is plain data, and all-zero is a valid value
+    let mut kernel: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    // SAFETY: the buffer and address outlive the call, and their lengths match
+    let sent = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            request.as_ptr().cast(),
+            request.len(),
+            0,
+            (&kernel as *const libc::sockaddr_nl).cast(),
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut buffer = vec![0u8; RECV_BUFFER_LEN];
+    loop {
+        // SAFETY: buffer is valid for writes of its full length
+        let received = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if parse_messages(&buffer[..received as usize], &mut on_message)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Netlink header of a dump request with a `payload_len` byte body
+fn request_header(payload_len: usize) -> Vec<u8> {
+    let len = NLMSG_HEADER_LEN + payload_len;
+    let mut buf = Vec::with_capacity(len);
+    buf.extend_from_slice(&(len as u32).to_ne_bytes());
+    buf.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    let flags = (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16;
+    buf.extend_from_slice(&flags.to_ne_bytes());
+    buf.extend_from_slice(&1u32.to_ne_bytes()); // sequence number
+    buf.extend_from_slice(&0u32.to_ne_bytes()); // port ID: filled in by the kernel
+    buf
+}
+
+/// A dump request for listening TCP sockets of `family`
+fn inet_request(family: u8) -> Vec<u8> {
+    let mut buf = request_header(REQUEST_LEN);
+    // struct inet_diag_req_v2: family, protocol, extensions, padding, states
+    buf.extend_from_slice(&[family, libc::IPPROTO_TCP as u8, 0, 0]);
+    buf.extend_from_slice(&(1u32 << TCP_LISTEN).to_ne_bytes());
+    // struct inet_diag_sockid: all zero to match every socket
+    buf.resize(NLMSG_HEADER_LEN + REQUEST_LEN, 0);
+    buf
+}
+
+/// A dump request for listening Unix sockets, with their names
+fn unix_request() -> Vec<u8> {
+    let mut buf = request_header(UNIX_REQUEST_LEN);
+    // struct unix_diag_req: family, protocol, padding, states, inode, show
+    buf.extend_from_slice(&[libc::AF_UNIX as u8, 0, 0, 0]);
+    buf.extend_from_slice(&(1u32 << TCP_LISTEN).to_ne_bytes());
+    buf.extend_from_slice(&0u32.to_ne_bytes());
+    buf.extend_from_slice(&UDIAG_SHOW_NAME.to_ne_bytes());
+    // Cookie: all ones to match every socket
+    buf.extend_from_slice(&[0xff; 8]);
+    buf
+}
+
+/// Pass the payload of each socket in one datagram of netlink messages to
+/// `on_message`, returning whether the dump is complete
+fn parse_messages(
+    mut data: &[u8],
+    on_message: &mut impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<bool> {
+    while data.len() >= NLMSG_HEADER_LEN {
+        let len = u32_at(data, 0) as usize;
+        let kind = u16::from_ne_bytes([data[4], data[5]]);
+        if len < NLMSG_HEADER_LEN || len > data.len() {
+            return Err(invalid("truncated netlink message"));
+        }
+        let payload = &data[NLMSG_HEADER_LEN..len];
+
+        match kind {
+            k if k == libc::NLMSG_DONE as u16 => return Ok(true),
+            k if k == libc::NLMSG_ERROR as u16 => {
+                if payload.len() < 4 {
+                    return Err(invalid("truncated netlink error"));
+                }
+                let errno = i32::from_ne_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                if errno != 0 {
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+            }
+            SOCK_DIAG_BY_FAMILY => on_message(payload)?,
+            _ => {}
+        }
+
+        // Messages are padded to 4 bytes
+        let next = (len + 3) & !3;
+        data = data.get(next..).unwrap_or_default();
+    }
+    Ok(false)
+}
+
+/// Parse a `struct inet_diag_msg`
+fn parse_diag_msg(msg: &[u8]) -> io::Result<Listener> {
+    if msg.len() < DIAG_MSG_LEN {
+        return Err(invalid("truncated inet_diag_msg"));
+    }
+    let family = msg[0];
+    // Ports and addresses are in network byte order
+    let port = u16::from_be_bytes([msg[4], msg[5]]);
+    let address = if i32::from(family) == libc::AF_INET {
+        IpAddr::V4(Ipv4Addr::new(msg[8], msg[9], msg[10], msg[11]))
+    } else {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&msg[8..24]);
+        IpAddr::V6(Ipv6Addr::from(octets))
+    };
+
+    Ok(Listener {
+        port,
+        address,
+        details: SocketDetails {
+            // For listening sockets the queues hold pending connections
+            // and the listen backlog
+            accept_queue: u32_at(msg, 56),
+            backlog: u32_at(msg, 60),
+            uid: u32_at(msg, 64),
+            inode: u32_at(msg, 68),
+        },
+    })
+}
+
+/// Parse a `struct unix_diag_msg` and its `UNIX_DIAG_NAME` attribute
+fn parse_unix_diag_msg(msg: &[u8]) -> io::Result<UnixListener> {
+    if msg.len() < UNIX_DIAG_MSG_LEN {
+        return Err(invalid("truncated unix_diag_msg"));
+    }
+    let inode = u32_at(msg, 4);
+
+    let mut path = String::new();
+    let mut attrs = &msg[UNIX_DIAG_MSG_LEN..];
+    while attrs.len() >= 4 {
+        let len = usize::from(u16::from_ne_bytes([attrs[0], attrs[1]]));
+        let kind = u16::from_ne_bytes([attrs[2], attrs[3]]);
+        if len < 4 || len > attrs.len() {
+            return Err(invalid("truncated netlink attribute"));
+        }
+        if kind == UNIX_DIAG_NAME {
+            path = unix_name(&attrs[4..len]);
+        }
+        // Attributes are padded to 4 bytes
+        attrs = attrs.get((len + 3) & !3..).unwrap_or_default();
+    }
+    Ok(UnixListener { path, inode })
+}
+
+/// Display form of a `sun_path`: abstract names start with a NUL byte and
+/// are shown with a leading `@`, like ss(8) does
+fn unix_name(raw: &[u8]) -> String {
+    match raw.split_first() {
+        Some((0, name)) => format!("@{}", String::from_utf8_lossy(name)),
+        _ => {
+            let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+            String::from_utf8_lossy(&raw[..end]).to_string()
+        }
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Maps socket inodes to the PID holding them, remembering which fd each
+/// inode was found at so the next scan can check it with one readlink
+/// instead of walking every process again
+#[derive(Debug, Default)]
+pub(crate) struct InodeOwners {
+    found: HashMap<u32, (u32, String)>,
+    /// Inodes no visible process held at the last walk, usually sockets of
+    /// other users; not searched for again while they stay open
+    hidden: HashSet<u32>,
+}
+
+impl InodeOwners {
+    /// PID of the process holding each of `inodes`, where it can be seen
+    pub(crate) fn resolve(&mut self, inodes: &[u32]) -> HashMap<u32, u32> {
+        let wanted: HashSet<u32> = inodes.iter().copied().collect();
+        self.found
+            .retain(|inode, (pid, fd)| wanted.contains(inode) && holds(*pid, fd, *inode));
+
+        self.hidden.retain(|inode| wanted.contains(inode));
+
+        let missing: HashSet<u32> = wanted
+            .into_iter()
+            .filter(|inode| !self.found.contains_key(inode) && !self.hidden.contains(inode))
+            .collect();
+        if !missing.is_empty() {
+            self.scan_proc(missing);
+        }
+
+        self.found
+            .iter()
+            .map(|(&inode, &(pid, _))| (inode, pid))
+            .collect()
+    }
+
+    /// Walk `/proc/*/fd` until every inode in `missing` has an owner
+    fn scan_proc(&mut self, mut missing: HashSet<u32>) {
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return;
+        };
+        self.walk(entries, &mut missing);
+        self.hidden.extend(missing);
+    }
+
+    fn walk(&mut self, entries: fs::ReadDir, missing: &mut HashSet<u32>) {
+        let mut pids: Vec<u32> = entries
+            .filter_map(|e| e.ok()?.file_name().to_str()?.parse().ok())
+            .collect();
+        // Prefer the parent when a socket is shared with forked children
+        pids.sort_unstable();
+
+        for pid in pids {
+            let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+                continue;
+            };
+            for fd in fds.flatten() {
+                let Some(inode) = fs::read_link(fd.path())
+                    .ok()
+                    .and_then(|link| socket_inode(link.to_str()?))
+                else {
+                    continue;
+                };
+                if missing.remove(&inode) {
+                    let fd = fd.file_name().to_string_lossy().to_string();
+                    self.found.insert(inode, (pid, fd));
+                    if missing.is_empty() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether `pid` still holds socket `inode` at `fd`
+fn holds(pid: u32, fd: &str, inode: u32) -> bool {
+    fs::read_link(format!("/proc/{pid}/fd/{fd}"))
+        .ok()
+        .and_then(|link| socket_inode(link.to_str()?))
+        == Some(inode)
+}
+
+/// Inode of an fd link like `socket:[12345]`
+fn socket_inode(link: &str) -> Option<u32> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(kind: u16, payload: &[u8]) -> Vec<u8> {
+        let len = NLMSG_HEADER_LEN + payload.len();
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(len as u32).to_ne_bytes());
+        buf.extend_from_slice(&kind.to_ne_bytes());
+        buf.extend_from_slice(&[0; 10]);
+        buf.extend_from_slice(payload);
+        buf.resize((len + 3) & !3, 0);
+        buf
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut diag = vec![0u8; DIAG_MSG_LEN];
+        diag[0] = libc::AF_INET as u8;
+        diag[1] = TCP_LISTEN as u8;
+        diag[4..6].copy_from_slice(&5432u16.to_be_bytes());
+        diag[8..12].copy_from_slice(&[127, 0, 0, 1]);
+        diag[56..60].copy_from_slice(&2u32.to_ne_bytes());
+        diag[60..64].copy_from_slice(&128u32.to_ne_bytes());
+        diag[64..68].copy_from_slice(&1000u32.to_ne_bytes());
+        diag[68..72].copy_from_slice(&98765u32.to_ne_bytes());
+
+        let mut data = message(SOCK_DIAG_BY_FAMILY, &diag);
+        let mut payloads = Vec::new();
+        let mut collect = |msg: &[u8]| {
+            payloads.push(msg.to_vec());
+            Ok(())
+        };
+        assert!(!parse_messages(&data, &mut collect).unwrap());
+
+        data.extend(message(libc::NLMSG_DONE as u16, &[0; 4]));
+        assert!(parse_messages(&data, &mut collect).unwrap());
+        // The second pass saw the same message again, then the end marker
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(
+            parse_diag_msg(&payloads[1]).unwrap(),
+            Listener {
+                port: 5432,
+                address: "127.0.0.1".parse().unwrap(),
+                details: SocketDetails {
+                    inode: 98765,
+                    uid: 1000,
+                    accept_queue: 2,
+                    backlog: 128,
+                },
+            }
+        );
+
+        let error = message(libc::NLMSG_ERROR as u16, &(-libc::EACCES).to_ne_bytes());
+        let err = parse_messages(&error, &mut |_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_parse_unix_diag_msg() {
+        let mut msg = vec![libc::AF_UNIX as u8, libc::SOCK_STREAM as u8, 10, 0];
+        msg.extend_from_slice(&4242u32.to_ne_bytes());
+        msg.extend_from_slice(&[0; 8]);
+        // A 3 byte UNIX_DIAG_SHOW attribute to skip, padded to 4
+        msg.extend_from_slice(&7u16.to_ne_bytes());
+        msg.extend_from_slice(&5u16.to_ne_bytes());
+        msg.extend_from_slice(&[1, 2, 3, 0]);
+        let name = b"/run/postgresql/.s.PGSQL.5432\0";
+        msg.extend_from_slice(&(4 + name.len() as u16).to_ne_bytes());
+        msg.extend_from_slice(&UNIX_DIAG_NAME.to_ne_bytes());
+        msg.extend_from_slice(name);
+
+        assert_eq!(
+            parse_unix_diag_msg(&msg).unwrap(),
+            UnixListener {
+                path: "/run/postgresql/.s.PGSQL.5432".to_string(),
+                inode: 4242,
+            }
+        );
+        assert!(parse_unix_diag_msg(&msg[..8]).is_err());
+    }
+
+    #[test]
+    fn test_unix_name() {
+        assert_eq!(unix_name(b"\0/tmp/.X11-unix/X0"), "@/tmp/.X11-unix/X0");
+        assert_eq!(unix_name(b"/run/docker.sock"), "/run/docker.sock");
+        assert_eq!(unix_name(b""), "");
+    }
+
+    #[test]
+    fn test_socket_inode() {
+        assert_eq!(socket_inode("socket:[12345]"), Some(12345));
+        assert_eq!(socket_inode("pipe:[12345]"), None);
+        assert_eq!(socket_inode("/dev/null"), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Servers still running a binary that was deleted or rebuilt.
+//!
+//! After a rebuild, a server that was not restarted keeps running the old
+//! executable: `/proc/<pid>/exe` then points at a `(deleted)` file, or at a
+//! file modified after the process started. The scanner caches executable
+//! paths for as long as a process lives, so the link is read afresh here on
+//! every scan.
+
+use port_checker::ports::PortInfo;
+use port_checker::signal;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Suffix the kernel adds to the target of an `exe` link whose file is gone
+const DELETED: &str = " (deleted)";
+
+/// Clock ticks per second of `/proc/<pid>/stat` times, fixed at 100
+/// (`USER_HZ`) for userspace on Linux
+const TICKS_PER_SEC: u64 = 100;
+
+/// Boot time is only known to the second, so start times may be early by
+/// up to this much
+const START_TIME_SLACK: Duration = Duration::from_secs(1);
+
+/// How long a restart waits for the old process to exit
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether each port's process runs a stale binary, in order
+pub fn stale_binaries(ports: &[PortInfo]) -> Vec<bool> {
+    let boot_time = fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|stat| parse_boot_time(&stat));
+    let mut by_pid: HashMap<u32, bool> = HashMap::new();
+    ports
+        .iter()
+        .map(|p| {
+            *by_pid
+                .entry(p.pid)
+                .or_insert_with(|| is_stale(p.pid, boot_time))
+        })
+        .collect()
+}
+
+fn is_stale(pid: u32, boot_time: Option<u64>) -> bool {
+    let Ok(target) = fs::read_link(format!("/proc/{pid}/exe")) else {
+        return false;
+    };
+    let target = target.to_string_lossy();
+    if target.ends_with(DELETED) {
+        return true;
+    }
+    // The binary as the process sees it, which may be in a container
+    let modified = fs::metadata(format!("/proc/{pid}/root{target}")).and_then(|m| m.modified());
+    let started = fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|stat| start_time(&stat, boot_time?));
+    match (modified, started) {
+        (Ok(modified), Some(started)) => modified > started + START_TIME_SLACK,
+        _ => false,
+    }
+}
+
+/// Boot time in seconds since the epoch, from the `btime` line of
+/// `/proc/stat`
+fn parse_boot_time(stat: &str) -> Option<u64> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|secs| secs.trim().parse().ok())
+}
+
+/// When a process started, from its `/proc/<pid>/stat` and the boot time.
+/// Fields are counted from the last `)`, as the command name may contain
+/// any character.
+fn start_time(stat: &str, boot_time: u64) -> Option<SystemTime> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // starttime is field 22 of the line, the 20th after the command name
+    let ticks: u64 = rest.split_whitespace().nth(19)?.parse().ok()?;
+    let millis = boot_time * 1000 + ticks * 1000 / TICKS_PER_SEC;
+    Some(UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// Terminate `port`'s process and start its command again, from the
+/// current binary at the same path, in the same directory and with the
+/// same environment. Returns the new PID.
+pub fn restart(port: &PortInfo) -> io::Result<u32> {
+    let exe = port
+        .exe_path
+        .as_deref()
+        .ok_or_else(|| io::Error::other("the executable is unknown"))?;
+    let exe = strip_deleted(exe);
+    // Check before stopping the old server that there is one to start
+    if !exe.is_file() {
+        return Err(io::Error::other(format!(
+            "{} no longer exists",
+            exe.display()
+        )));
+    }
+    let environ = fs::read(format!("/proc/{}/environ", port.pid))?;
+
+    signal::terminate(port.pid)?;
+    let deadline = SystemTime::now() + EXIT_TIMEOUT;
+    while Path::new(&format!("/proc/{}", port.pid)).exists() {
+        if SystemTime::now() > deadline {
+            return Err(io::Error::other(format!("PID {} did not exit", port.pid)));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let mut command = Command::new(&exe);
+    if let Some((arg0, args)) = port.cmd_args.split_first() {
+        command.arg0(arg0).args(args);
+    }
+    if let Some(cwd) = &port.cwd {
+        command.current_dir(cwd);
+    }
+    command.env_clear();
+    for var in environ.split(|&b| b == 0).filter(|var| !var.is_empty()) {
+        if let Some(eq) = var.iter().position(|&b| b == b'=') {
+            command.env(
+                OsStr::from_bytes(&var[..eq]),
+                OsStr::from_bytes(&var[eq + 1..]),
+            );
+        }
+    }
+    // Detached from the TUI's terminal and process group, so quitting
+    // port-checker leaves the server running
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    let pid = child.id();
+    // Reap the server whenever it exits
+    thread::spawn(move || child.wait());
+    Ok(pid)
+}
+
+/// `path` without the kernel's `(deleted)` suffix
+fn strip_deleted(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    PathBuf::from(text.strip_suffix(DELETED).unwrap_or(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_time() {
+        let stat = "cpu  1 2 3\nbtime 1700000000\nprocesses 4\n";
+        let boot_time = parse_boot_time(stat).unwrap();
+        assert_eq!(boot_time, 1_700_000_000);
+
+        let stat = "4242 (next-server (v1)) S 1 4200 4100 0 -1 4194560 1200 0 0 0 12 3 0 0 20 0 \
+                    11 0 123456 1000 50";
+        assert_eq!(
+            start_time(stat, boot_time),
+            Some(UNIX_EPOCH + Duration::from_millis(1_700_001_234_560))
+        );
+        assert_eq!(start_time("4242 (node) S 1", boot_time), None);
+    }
+
+    #[test]
+    fn test_is_stale() {
+        // A binary modified before this test started is current
+        let boot_time = fs::read_to_string("/proc/stat")
+            .ok()
+            .and_then(|stat| parse_boot_time(&stat));
+        assert!(!is_stale(std::process::id(), boot_time));
+        assert_eq!(
+            strip_deleted(Path::new("/srv/api/target/debug/api (deleted)")),
+            Path::new("/srv/api/target/debug/api")
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! systemd units behind listeners, and `systemctl` actions on them.
+//!
+//! Killing a service's process only makes systemd restart it, so rows name
+//! the unit to stop instead. A process's unit comes from its cgroup in
+//! `/proc/<pid>/cgroup`. Sockets held by the service manager itself are
+//! socket-activated; their `.socket` unit comes from `systemctl list-sockets`.
+
+use port_checker::ports::PortInfo;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::process::Command;
+
+/// Which service manager a unit belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    System,
+    /// The per-user manager of `uid`, run as `user@<uid>.service`
+    User {
+        uid: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unit {
+    /// Full unit name, such as `nginx.service` or `sshd.socket`
+    pub name: String,
+    pub scope: Scope,
+    /// Services a socket unit starts, for socket-activated listeners
+    pub activates: Vec<String>,
+}
+
+impl Unit {
+    pub fn scope_label(&self) -> String {
+        match self.scope {
+            Scope::System => "system".to_string(),
+            Scope::User { uid } => format!("user {uid}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitAction {
+    Stop,
+    Restart,
+    Status,
+}
+
+impl UnitAction {
+    /// Past tense for status messages
+    pub fn done(self) -> &'static str {
+        match self {
+            UnitAction::Stop => "Stopped",
+            UnitAction::Restart => "Restarted",
+            UnitAction::Status => "Checked",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            UnitAction::Stop => "stop",
+            UnitAction::Restart => "restart",
+            UnitAction::Status => "status",
+        }
+    }
+}
+
+/// `systemctl` arguments to reach `scope`'s manager. Another user's
+/// manager is reached through `--machine=<user>@`.
+fn scope_args(scope: Scope) -> Vec<String> {
+    match scope {
+        Scope::System => vec![],
+        Scope::User { uid } if uid == nix::unistd::geteuid().as_raw() => {
+            vec!["--user".to_string()]
+        }
+        Scope::User { uid } => {
+            let user = nix::unistd::User::from_uid(uid.into())
+                .ok()
+                .flatten()
+                .map_or_else(|| uid.to_string(), |u| u.name);
+            vec!["--user".to_string(), format!("--machine={user}@")]
+        }
+    }
+}
+
+/// `systemctl` invocation running `action` on `unit`
+pub fn command(unit: &Unit, action: UnitAction) -> Command {
+    let mut command = Command::new("systemctl");
+    command.args(scope_args(unit.scope));
+    if action != UnitAction::Status {
+        // A password prompt would garble the TUI; such failures are reported
+        command.arg("--no-ask-password");
+    }
+    command.args([action.verb(), unit.name.as_str()]);
+    command
+}
+
+/// Stop or restart `unit`, waiting for `systemctl` to finish
+pub fn run(unit: &Unit, action: UnitAction) -> Result<(), String> {
+    let output = command(unit, action)
+        .output()
+        .map_err(|e| format!("systemctl: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(stderr.lines().find(|l| !l.trim().is_empty()).map_or_else(
+        || format!("systemctl exited with {}", output.status),
+        str::to_string,
+    ))
+}
+
+/// Unit of each port in `ports`, in order
+pub fn units(ports: &[PortInfo]) -> Vec<Option<Unit>> {
+    let mut by_pid: HashMap<u32, Option<Unit>> = HashMap::new();
+    let mut sockets: HashMap<Scope, Vec<SocketUnit>> = HashMap::new();
+    ports
+        .iter()
+        .map(|p| {
+            if let Some(scope) = manager_scope(p) {
+                let listing = sockets.entry(scope).or_insert_with(|| list_sockets(scope));
+                return listing.iter().find(|s| s.holds(p)).map(|s| Unit {
+                    name: s.unit.clone(),
+                    scope,
+                    activates: s.activates.clone(),
+                });
+            }
+            by_pid
+                .entry(p.pid)
+                .or_insert_with(|| unit_of(p.pid))
+                .clone()
+        })
+        .collect()
+}
+
+/// Service unit `pid` runs in
+fn unit_of(pid: u32) -> Option<Unit> {
+    let cgroup = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    parse_cgroup(&cgroup)
+}
+
+/// Path of the systemd cgroup in the contents of `/proc/<pid>/cgroup`: the
+/// unified hierarchy on cgroup v2, or the named systemd one on v1
+fn cgroup_path(cgroup: &str) -> Option<&str> {
+    cgroup.lines().find_map(|line| {
+        let (_, rest) = line.split_once(':')?;
+        let (controllers, path) = rest.split_once(':')?;
+        (controllers.is_empty() || controllers == "name=systemd").then_some(path)
+    })
+}
+
+/// UID of a `user@<uid>.service` cgroup part
+fn user_manager(part: &str) -> Option<u32> {
+    part.strip_prefix("user@")?
+        .strip_suffix(".service")?
+        .parse()
+        .ok()
+}
+
+/// Unit of the systemd cgroup in the contents of `/proc/<pid>/cgroup`. Only
+/// a `.service` that is the innermost unit counts, in a user manager when it
+/// is nested under `user@<uid>.service`. Processes in scopes, such as login
+/// sessions, terminals and tmux, and the user manager itself have no unit
+/// to stop.
+fn parse_cgroup(cgroup: &str) -> Option<Unit> {
+    let parts: Vec<&str> = cgroup_path(cgroup)?
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .collect();
+    let unit = parts
+        .iter()
+        .rposition(|p| p.ends_with(".service") || p.ends_with(".scope"))?;
+    let name = parts[unit];
+    if name.ends_with(".scope") || user_manager(name).is_some() {
+        return None;
+    }
+    let scope = parts[..unit]
+        .iter()
+        .rev()
+        .find_map(|p| user_manager(p))
+        .map_or(Scope::System, |uid| Scope::User { uid });
+    Some(Unit {
+        name: name.to_string(),
+        scope,
+        activates: vec![],
+    })
+}
+
+/// UID of the user manager whose own process has the contents of
+/// `/proc/<pid>/cgroup`: it runs in `user@<uid>.service`, or in its
+/// `init.scope`
+fn parse_user_manager(cgroup: &str) -> Option<u32> {
+    let parts: Vec<&str> = cgroup_path(cgroup)?
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .collect();
+    match parts[..] {
+        [.., manager, "init.scope"] | [.., manager] => user_manager(manager),
+        [] => None,
+    }
+}
+
+/// Which manager holds `port` for socket activation, if a manager does:
+/// PID 1, or a user's `systemd --user`
+fn manager_scope(port: &PortInfo) -> Option<Scope> {
+    if port.pid == 1 {
+        return Some(Scope::System);
+    }
+    if port.process_name != "systemd" {
+        return None;
+    }
+    let cgroup = fs::read_to_string(format!("/proc/{}/cgroup", port.pid)).ok()?;
+    parse_user_manager(&cgroup).map(|uid| Scope::User { uid })
+}
+
+/// One line of `systemctl list-sockets`
+#[derive(Debug, PartialEq, Eq)]
+struct SocketUnit {
+    /// Address the socket listens on, or `None` for any address
+    address: Option<IpAddr>,
+    port: u16,
+    unit: String,
+    activates: Vec<String>,
+}
+
+impl SocketUnit {
+    fn holds(&self, port: &PortInfo) -> bool {
+        self.port == port.port
+            && self
+                .address
+                .is_none_or(|a| a.is_unspecified() || a == port.address)
+    }
+}
+
+/// TCP sockets of `scope`'s manager; empty when systemctl is unavailable
+fn list_sockets(scope: Scope) -> Vec<SocketUnit> {
+    let output = Command::new("systemctl")
+        .args(scope_args(scope))
+        .args([
+            "list-sockets",
+            "--all",
+            "--full",
+            "--no-legend",
+            "--no-pager",
+        ])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_list_sockets(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => vec![],
+    }
+}
+
+/// Parse `systemctl list-sockets --no-legend` output, keeping sockets
+/// with a port: `LISTEN UNIT ACTIVATES...`, where LISTEN is `[::]:22`,
+/// `127.0.0.1:631` or a bare port
+fn parse_list_sockets(output: &str) -> Vec<SocketUnit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let listen = fields.next()?;
+            let unit = fields.next()?.to_string();
+            let (address, port) = match listen.rsplit_once(':') {
+                Some((address, port)) => {
+                    let address = address.trim_start_matches('[').trim_end_matches(']');
+                    (Some(address.parse().ok()?), port.parse().ok()?)
+                }
+                None => (None, listen.parse().ok()?),
+            };
+            Some(SocketUnit {
+                address,
+                port,
+                unit,
+                activates: fields.map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cgroup() {
+        let unit = parse_cgroup("0::/system.slice/nginx.service\n").unwrap();
+        assert_eq!(unit.name, "nginx.service");
+        assert_eq!(unit.scope, Scope::System);
+
+        let unit = parse_cgroup(
+            "0::/user.slice/user-1000.slice/user@1000.service/app.slice/vite.service\n",
+        )
+        .unwrap();
+        assert_eq!(unit.name, "vite.service");
+        assert_eq!(unit.scope, Scope::User { uid: 1000 });
+
+        // cgroup v1 keeps the systemd hierarchy under name=systemd
+        let v1 = "12:cpu,cpuacct:/\n1:name=systemd:/system.slice/postgresql.service\n";
+        assert_eq!(parse_cgroup(v1).unwrap().name, "postgresql.service");
+
+        // Login sessions and containers are scopes, not services
+        assert_eq!(
+            parse_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+
+        // Terminals and tmux start servers in scopes under the user manager,
+        // which must not be taken for their unit
+        let user = "0::/user.slice/user-1000.slice/user@1000.service";
+        for scope in [
+            "app.slice/tmux-spawn-4f3c1b2a.scope",
+            "app.slice/app-org.gnome.Terminal.slice/vte-spawn-9a8b.scope",
+        ] {
+            assert_eq!(parse_cgroup(&format!("{user}/{scope}\n")), None, "{scope}");
+        }
+        assert_eq!(parse_cgroup(&format!("{user}/init.scope\n")), None);
+        assert_eq!(parse_cgroup(&format!("{user}\n")), None);
+    }
+
+    #[test]
+    fn test_parse_user_manager() {
+        let user = "0::/user.slice/user-1000.slice/user@1000.service";
+        assert_eq!(
+            parse_user_manager(&format!("{user}/init.scope\n")),
+            Some(1000)
+        );
+        assert_eq!(parse_user_manager(&format!("{user}\n")), Some(1000));
+        assert_eq!(
+            parse_user_manager(&format!("{user}/app.slice/tmux-spawn-1.scope\n")),
+            None
+        );
+        assert_eq!(parse_user_manager("0::/init.scope\n"), None);
+    }
+
+    #[test]
+    fn test_parse_list_sockets() {
+        let output = "\
+[::]:22                       sshd.socket          sshd.service
+127.0.0.1:631                 cups.socket          cups.service
+/run/dbus/system_bus_socket   dbus.socket          dbus.service
+8080                          app.socket           app.service app-worker.service
+";
+        let sockets = parse_list_sockets(output);
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(
+            sockets[0],
+            SocketUnit {
+                address: Some("::".parse().unwrap()),
+                port: 22,
+                unit: "sshd.socket".to_string(),
+                activates: vec!["sshd.service".to_string()],
+            }
+        );
+        assert_eq!(sockets[1].address, Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(sockets[2].address, None);
+        assert_eq!(sockets[2].activates.len(), 2);
+    }
+}
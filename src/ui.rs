@@ -1,79 +1,183 @@
-use crate::app::{App, PopupButton};
+use crate::app::{user_label, App, Grouping, Owner, PopupButton, Source, View};
+use crate::elevate::{self, Elevation};
+use crate::recognize;
+use crate::snapshot::DiffKind;
 use ratatui::{
-    layout::{Constraint, Layout, Rect, Flex},
+    layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
     Frame,
 };
 
+/// Height of the lifespan panel below the table in timeline mode
+const TIMELINE_HEIGHT: u16 = 12;
+
+/// Width of the port/process label in front of each lifespan bar
+const TIMELINE_LABEL_WIDTH: usize = 24;
+
+/// Deepest indent of the process tree, so long ancestries stay readable
+const MAX_TREE_INDENT: usize = 8;
+
 pub fn render(frame: &mut Frame, app: &mut App) {
     let area = frame.area();
 
     let chunks = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).split(area);
 
-    render_table(frame, app, chunks[0]);
+    if matches!(app.source, Source::Timeline { .. }) {
+        let parts = Layout::vertical([Constraint::Min(0), Constraint::Length(TIMELINE_HEIGHT)])
+            .split(chunks[0]);
+        render_table(frame, app, parts[0]);
+        render_timeline(frame, app, parts[1]);
+    } else {
+        render_table(frame, app, chunks[0]);
+    }
     render_footer(frame, app, chunks[1]);
 
     if app.show_terminate_popup {
         render_terminate_popup(frame, app);
     }
+    if let Some(elevation) = &app.elevation_prompt {
+        render_elevation_prompt(frame, elevation);
+    }
+    if let Some(servers) = &app.cleanup_prompt {
+        render_cleanup_prompt(frame, servers);
+    }
 }
 
 fn render_table(frame: &mut Frame, app: &mut App, area: Rect) {
     let header_style = Style::default().add_modifier(Modifier::BOLD);
-    let header = Row::new(vec![
-        Cell::from("Port").style(header_style),
-        Cell::from("PID").style(header_style),
-        Cell::from("Process").style(header_style),
-        Cell::from("Path").style(header_style),
-    ])
-    .height(1)
-    .bottom_margin(1);
-
-    let rows: Vec<Row> = app
-        .ports
-        .iter()
-        .map(|p| {
-            let path = p
-                .exe_path
-                .as_ref()
-                .map(|path| path.to_string_lossy().to_string())
-                .unwrap_or_else(|| "-".to_string());
-
-            // Apply horizontal scroll offset to path
-            let scrolled_path = if app.scroll_offset as usize >= path.len() {
-                String::new()
-            } else {
-                path.chars().skip(app.scroll_offset as usize).collect()
-            };
+    let show_diff = matches!(app.source, Source::Diff { .. });
+
+    let (headers, mut widths) = match app.view {
+        View::Tcp => (
+            vec!["Port", "Address", "PID", "User", "Process", "Path"],
+            vec![
+                // Room for "▾ 12 ports" and "└ 65535" when grouped
+                Constraint::Length(if app.grouping == Grouping::None {
+                    8
+                } else {
+                    11
+                }),
+                Constraint::Length(16),
+                Constraint::Length(10),
+                Constraint::Length(12),
+                Constraint::Length(24),
+                Constraint::Fill(1),
+            ],
+        ),
+        View::Unix => (
+            vec!["PID", "User", "Process", "Working Dir", "Socket"],
+            vec![
+                Constraint::Length(10),
+                Constraint::Length(12),
+                Constraint::Length(20),
+                Constraint::Length(32),
+                Constraint::Fill(1),
+            ],
+        ),
+    };
+    let mut header_cells: Vec<Cell> = headers
+        .into_iter()
+        .map(|h| Cell::from(h).style(header_style))
+        .collect();
+    let show_host = matches!(app.source, Source::Remote { .. });
 
-            Row::new(vec![
-                Cell::from(p.port.to_string()),
-                Cell::from(p.pid.to_string()),
-                Cell::from(p.process_name.clone()),
-                Cell::from(scrolled_path),
-            ])
+    if app.view == View::Tcp && app.project_column {
+        let path = header_cells.len() - 1;
+        for (header, width) in [("Branch", 14), ("Project", 16)] {
+            header_cells.insert(path, Cell::from(header).style(header_style));
+            widths.insert(path, Constraint::Length(width));
+        }
+    }
+    let show_unit = app.view == View::Tcp && app.unit_column;
+    if show_unit {
+        let path = header_cells.len() - 1;
+        header_cells.insert(path, Cell::from("Unit").style(header_style));
+        widths.insert(path, Constraint::Length(24));
+    }
+    if app.view == View::Tcp && app.flags_column {
+        let path = header_cells.len() - 1;
+        header_cells.insert(path, Cell::from("Flags").style(header_style));
+        widths.insert(path, Constraint::Length(14));
+    }
+    let show_namespace = app.view == View::Tcp && app.namespace_column;
+    if show_namespace {
+        header_cells.insert(0, Cell::from("Namespace").style(header_style));
+    }
+    if show_host {
+        header_cells.insert(0, Cell::from("Host").style(header_style));
+    }
+    if show_diff {
+        header_cells.insert(0, Cell::from(""));
+    }
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    // Build rows only for the window that fits, so huge lists cost the
+    // same per frame as short ones
+    let visible = usize::from(area.height.saturating_sub(4)).max(1);
+    let offset = window_offset(
+        app.table_state.offset(),
+        app.table_state.selected(),
+        visible,
+        app.rows.len(),
+    );
+    *app.table_state.offset_mut() = offset;
+    let end = (offset + visible).min(app.rows.len());
+
+    let rows: Vec<Row> = (offset..end)
+        .map(|i| {
+            let text = &app.rows[i];
+            // Apply horizontal scroll offset to the path column
+            let scrolled = text
+                .scrolled
+                .char_indices()
+                .nth(app.scroll_offset as usize)
+                .map_or("", |(start, _)| &text.scrolled[start..]);
+
+            let mut cells: Vec<Cell> = text.cells.iter().map(|c| Cell::from(c.as_str())).collect();
+            cells.push(Cell::from(scrolled));
+
+            let diff = app.shown.get(i).and_then(|&port| app.diff.get(port));
+            match diff.filter(|_| show_diff) {
+                Some(entry) => {
+                    cells.insert(0, Cell::from(entry.kind.marker()));
+                    Row::new(cells).style(diff_style(entry.kind))
+                }
+                None => Row::new(cells).style(owner_style(text.owner)),
+            }
         })
         .collect();
 
-    let widths = [
-        Constraint::Length(8),
-        Constraint::Length(10),
-        Constraint::Length(20),
-        Constraint::Fill(1),
-    ];
+    if show_namespace {
+        widths.insert(0, Constraint::Length(18));
+    }
+    if show_host {
+        widths.insert(0, Constraint::Length(16));
+    }
+    if show_diff {
+        widths.insert(0, Constraint::Length(1));
+    }
+
+    let title = app.title();
+
+    let mut block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::White));
 
-    let title = format!(" Listening TCP Ports ({}) ", app.ports.len());
+    // Warnings go on the bottom border so they never hide rows
+    let warnings = app.warning_summary();
+    if !warnings.is_empty() {
+        block = block.title_bottom(Span::styled(
+            format!(" \u{26a0} {} ", warnings.join(" | ")),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
 
     let table = Table::new(rows, widths)
         .header(header)
-        .block(
-            Block::default()
-                .title(title)
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::White)),
-        )
+        .block(block)
         .row_highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
@@ -81,11 +185,14 @@ fn render_table(frame: &mut Frame, app: &mut App, area: Rect) {
         )
         .highlight_symbol(">> ");
 
-    frame.render_stateful_widget(table, area, &mut app.table_state);
+    // The table only holds the window, so select relative to its start
+    let mut window_state =
+        TableState::default().with_selected(app.table_state.selected().map(|i| i - offset));
+    frame.render_stateful_widget(table, area, &mut window_state);
 
     if let Some(error) = &app.error {
-        let error_msg = Paragraph::new(format!("Error: {error}"))
-            .style(Style::default().fg(Color::Red));
+        let error_msg =
+            Paragraph::new(format!("Error: {error}")).style(Style::default().fg(Color::Red));
         let error_area = Rect {
             x: area.x + 2,
             y: area.y + 3,
@@ -96,8 +203,126 @@ fn render_table(frame: &mut Frame, app: &mut App, area: Rect) {
     }
 }
 
+/// First row to show so that `selected` stays within `visible` rows,
+/// scrolling as little as possible from the previous `offset`
+fn window_offset(offset: usize, selected: Option<usize>, visible: usize, len: usize) -> usize {
+    let mut offset = offset.min(len.saturating_sub(visible));
+    if let Some(selected) = selected {
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + visible {
+            offset = selected + 1 - visible;
+        }
+    }
+    offset
+}
+
+fn render_timeline(frame: &mut Frame, app: &App, area: Rect) {
+    let Source::Timeline { history, at, .. } = &app.source else {
+        return;
+    };
+
+    let block = Block::default()
+        .title(" Lifespans ([ ] step, { } prev/next event, -/+ step size) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::White));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some((first, _)) = history.span() else {
+        return;
+    };
+    let last = crate::snapshot::unix_now().max(*at);
+    let bar_width = (inner.width as usize).saturating_sub(TIMELINE_LABEL_WIDTH + 1);
+    if bar_width == 0 || inner.height == 0 {
+        return;
+    }
+
+    // Each bar column covers an equal slice of the recorded time range
+    let range = (last - first).max(1);
+    let column_of =
+        |t: u64| ((t - first) as u128 * (bar_width as u128 - 1) / range as u128) as usize;
+    let cursor = column_of((*at).clamp(first, last));
+
+    // One row per port, open whenever any of its listeners was
+    let spans = history.lifespans();
+    let mut ports: Vec<u16> = spans.iter().map(|s| s.port.port).collect();
+    ports.dedup();
+
+    let selected_port = app.get_selected_port().map(|p| p.port);
+    let first_row = selected_port
+        .and_then(|port| ports.iter().position(|&p| p == port))
+        .map(|i| i.saturating_sub(inner.height as usize - 1))
+        .unwrap_or(0);
+
+    let open_style = Style::default().fg(Color::Green);
+    let cursor_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut lines = Vec::new();
+    for &port in ports.iter().skip(first_row).take(inner.height as usize) {
+        let port_spans: Vec<_> = spans.iter().filter(|s| s.port.port == port).collect();
+        let name = &port_spans[port_spans.len() - 1].port.process_name;
+
+        let mut bar = vec![false; bar_width];
+        for span in &port_spans {
+            let start = column_of(span.opened.max(first));
+            // A close recorded before its open, say after the clock was
+            // set back, still marks the opening column
+            let end = column_of(span.closed.unwrap_or(last).clamp(first, last)).max(start);
+            for open in &mut bar[start..=end] {
+                *open = true;
+            }
+        }
+
+        let label: String = format!("{port:<6} {name}")
+            .chars()
+            .take(TIMELINE_LABEL_WIDTH)
+            .collect();
+        let label = format!("{label:<width$} ", width = TIMELINE_LABEL_WIDTH);
+        let label_style = if Some(port) == selected_port {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+
+        let mut line = vec![Span::styled(label, label_style)];
+        for (col, open) in bar.into_iter().enumerate() {
+            let span = match (col == cursor, open) {
+                (true, _) => Span::styled("\u{2502}", cursor_style),
+                (false, true) => Span::styled("\u{2588}", open_style),
+                (false, false) => Span::styled("\u{00b7}", Style::default().fg(Color::DarkGray)),
+            };
+            line.push(span);
+        }
+        lines.push(Line::from(line));
+    }
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn diff_style(kind: DiffKind) -> Style {
+    match kind {
+        DiffKind::Added => Style::default().fg(Color::Green),
+        DiffKind::Removed => Style::default().fg(Color::Red),
+        DiffKind::Changed => Style::default().fg(Color::Yellow),
+        DiffKind::Unchanged => Style::default().fg(Color::DarkGray),
+    }
+}
+
+/// Rows of root and of other users stand out from your own
+fn owner_style(owner: Owner) -> Style {
+    match owner {
+        Owner::Root => Style::default().fg(Color::LightRed),
+        Owner::Other => Style::default().fg(Color::LightBlue),
+        Owner::Mine | Owner::Unknown => Style::default(),
+    }
+}
+
 fn render_footer(frame: &mut Frame, app: &App, area: Rect) {
-    let key_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let key_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
     let status_style = Style::default().fg(Color::Cyan);
 
     let mut spans = vec![
@@ -106,6 +331,8 @@ fn render_footer(frame: &mut Frame, app: &App, area: Rect) {
         Span::raw(" quit  "),
         Span::styled("r", key_style),
         Span::raw(" refresh  "),
+        Span::styled("s", key_style),
+        Span::raw(" save  "),
         Span::styled("Enter/t", key_style),
         Span::raw(" details  "),
         Span::styled("\u{2190}/h", key_style),
@@ -118,6 +345,55 @@ fn render_footer(frame: &mut Frame, app: &App, area: Rect) {
         Span::raw(" down"),
     ];
 
+    if matches!(app.source, Source::Live) {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled("u", key_style));
+        spans.push(Span::raw(match app.view {
+            View::Tcp => " unix sockets",
+            View::Unix => " tcp ports",
+        }));
+    }
+
+    let orphans = app.orphans.iter().filter(|&&orphan| orphan).count();
+    if (orphans > 0 || app.orphans_only) && app.view == View::Tcp {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled("o", key_style));
+        spans.push(Span::raw(format!(" orphans ({orphans})  ")));
+        spans.push(Span::styled("O", key_style));
+        spans.push(Span::raw(" clean up"));
+    }
+
+    if app.namespace_column && app.view == View::Tcp {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled("n", key_style));
+        spans.push(Span::raw(format!(
+            " netns: {}",
+            app.namespace_filter.label()
+        )));
+    }
+
+    if app.can_elevate() && !app.warnings.is_empty() {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled("E", key_style));
+        spans.push(Span::raw(" rescan as root"));
+    }
+
+    if let Some(spinner) = app.spinner() {
+        spans.push(Span::raw("  |  "));
+        spans.push(Span::styled(format!("{spinner} Scanning"), status_style));
+        spans.push(Span::raw(" ("));
+        spans.push(Span::styled("Esc", key_style));
+        spans.push(Span::raw(" cancel)"));
+    }
+
+    if let Some((spinner, name)) = app.restarting() {
+        spans.push(Span::raw("  |  "));
+        spans.push(Span::styled(
+            format!("{spinner} Restarting {name}"),
+            status_style,
+        ));
+    }
+
     if let Some(status) = &app.status_message {
         spans.push(Span::raw("  |  "));
         spans.push(Span::styled(status.clone(), status_style));
@@ -130,55 +406,188 @@ fn render_footer(frame: &mut Frame, app: &App, area: Rect) {
 fn render_terminate_popup(frame: &mut Frame, app: &App) {
     let area = frame.area();
 
-    // Calculate popup size - wider and taller to show more content
+    // Calculate popup width - wider to show more content; the height
+    // follows the details below
     let popup_width = 80.min(area.width.saturating_sub(4));
-    let popup_height = 24.min(area.height.saturating_sub(4));
 
-    // Center the popup
-    let popup_area = centered_rect(popup_width, popup_height, area);
+    // Never zero, or wrap_text would loop forever on tiny terminals
+    let content_width = (popup_width as usize).saturating_sub(4).max(1);
+    let label = |text| Span::styled(text, Style::default().add_modifier(Modifier::BOLD));
 
-    // Clear the background
-    frame.render_widget(Clear, popup_area);
+    // Listener-specific lines, then the owning process shared by both views
+    let selected = if let Some(p) = app.get_selected_port() {
+        let mut lines = vec![Line::from(vec![
+            label("Process: "),
+            Span::raw(p.process_name.as_str()),
+        ])];
+        if let Some(runs) = recognize::label(&p.cmd_args) {
+            lines.push(Line::from(vec![label("Runs:    "), Span::raw(runs)]));
+        }
+
+        if let Some(host) = app.selected_port_index().and_then(|i| app.host_name(i)) {
+            lines.push(Line::from(vec![label("Host:    "), Span::raw(host)]));
+        }
+
+        let group_ports: Option<Vec<String>> = app
+            .get_selected_group()
+            .filter(|group| group.len() > 1)
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|&i| app.ports[i].port.to_string())
+                    .collect()
+            });
+
+        lines.extend([
+            Line::from(vec![label("PID:     "), Span::raw(p.pid.to_string())]),
+            Line::from(vec![
+                label("User:    "),
+                Span::raw(user_label(p.uid, p.user.as_deref(), true)),
+            ]),
+        ]);
+        match group_ports {
+            Some(ports) => lines.push(Line::from(vec![
+                label("Ports:   "),
+                Span::raw(ports.join(", ")),
+            ])),
+            None => lines.extend([
+                Line::from(vec![label("Port:    "), Span::raw(p.port.to_string())]),
+                Line::from(vec![label("Address: "), Span::raw(p.address.to_string())]),
+            ]),
+        }
 
-    // Get selected port info
-    let port_info = app.get_selected_port();
-    let content_width = popup_width as usize - 4;
+        if let Some(namespace) = &p.namespace {
+            lines.push(Line::from(vec![
+                label("Netns:   "),
+                Span::raw(format!("{} (inode {})", namespace.name, namespace.inode)),
+            ]));
+        }
 
-    let (title, details) = if let Some(p) = port_info {
-        let path_str = p.exe_path
+        if let Some(socket) = &p.socket {
+            lines.push(Line::from(vec![
+                label("Socket:  "),
+                Span::raw(format!(
+                    "inode {}, uid {}, queue {}/{}",
+                    socket.inode, socket.uid, socket.accept_queue, socket.backlog
+                )),
+            ]));
+        }
+
+        if let Some(i) = app.selected_port_index() {
+            let flags = app.flags(i);
+            if !flags.is_empty() {
+                lines.push(Line::from(vec![
+                    label("Flags:   "),
+                    Span::raw(flags.join(", ")),
+                ]));
+            }
+        }
+
+        if let Some(project) = app.get_selected_project() {
+            lines.push(Line::from(vec![
+                label("Project: "),
+                Span::raw(format!("{} ({})", project.name, project.root.display())),
+            ]));
+            if let Some(branch) = &project.branch {
+                lines.push(Line::from(vec![
+                    label("Branch:  "),
+                    Span::raw(branch.as_str()),
+                ]));
+            }
+            if app.get_selected_project_group().is_some() {
+                let processes: Vec<String> = app
+                    .project_pids()
+                    .into_iter()
+                    .filter_map(|pid| app.ports.iter().find(|p| p.pid == pid))
+                    .map(|p| format!("{} ({})", p.process_name, p.pid))
+                    .collect();
+                lines.push(Line::from(vec![
+                    label("Stops:   "),
+                    Span::raw(processes.join(", ")),
+                ]));
+            }
+        }
+
+        if let Some(unit) = app.get_selected_unit() {
+            let mut unit_line = vec![
+                label("Unit:    "),
+                Span::raw(format!("{} ({})", unit.name, unit.scope_label())),
+            ];
+            if !unit.activates.is_empty() {
+                unit_line.push(Span::raw(format!(
+                    ", activates {}",
+                    unit.activates.join(" ")
+                )));
+            }
+            lines.push(Line::from(unit_line));
+        }
+
+        if let Some(container) = app.get_selected_container() {
+            lines.extend([
+                Line::from(""),
+                Line::from(vec![
+                    label("Container: "),
+                    Span::raw(format!("{} ({})", container.name, container.short_id())),
+                ]),
+                Line::from(vec![
+                    label("Image:     "),
+                    Span::raw(container.image.as_str()),
+                ]),
+                Line::from(vec![
+                    label("Project:   "),
+                    Span::raw(container.project.as_deref().unwrap_or("-")),
+                ]),
+            ]);
+        }
+
+        let title = format!(" Process Details (Port {}) ", p.port);
+        Some((title, lines, (&p.exe_path, &p.cwd, &p.cmd_args)))
+    } else {
+        app.get_selected_unix().map(|u| {
+            let mut lines = vec![
+                Line::from(vec![label("Process: "), Span::raw(u.process_name.as_str())]),
+                Line::from(vec![label("PID:     "), Span::raw(u.pid.to_string())]),
+                Line::from(vec![
+                    label("User:    "),
+                    Span::raw(user_label(u.uid, u.user.as_deref(), true)),
+                ]),
+                Line::from(vec![label("Inode:   "), Span::raw(u.inode.to_string())]),
+                Line::from(""),
+                Line::from(vec![label("Socket:")]),
+            ];
+            for line in wrap_text(&u.path, content_width) {
+                lines.push(Line::from(line));
+            }
+
+            let title = " Process Details (Unix Socket) ".to_string();
+            (title, lines, (&u.exe_path, &u.cwd, &u.cmd_args))
+        })
+    };
+
+    let (title, details) = if let Some((title, mut lines, (exe_path, cwd, cmd_args))) = selected {
+        let path_str = exe_path
             .as_ref()
             .map(|path| path.to_string_lossy().to_string())
             .unwrap_or_else(|| "-".to_string());
 
-        let cwd_str = p.cwd
+        let cwd_str = cwd
             .as_ref()
             .map(|path| path.to_string_lossy().to_string())
             .unwrap_or_else(|| "-".to_string());
 
-        let cmd_str = if p.cmd_args.is_empty() {
+        let cmd_str = if cmd_args.is_empty() {
             "-".to_string()
         } else {
-            p.cmd_args.join(" ")
+            cmd_args.join(" ")
         };
 
-        let mut lines = vec![
-            Line::from(vec![
-                Span::styled("Process: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(&p.process_name),
-            ]),
-            Line::from(vec![
-                Span::styled("PID:     ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(p.pid.to_string()),
-            ]),
-            Line::from(vec![
-                Span::styled("Port:    ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(p.port.to_string()),
-            ]),
+        lines.extend([
             Line::from(""),
-            Line::from(vec![
-                Span::styled("Path:", Style::default().add_modifier(Modifier::BOLD)),
-            ]),
-        ];
+            Line::from(vec![Span::styled(
+                "Path:",
+                Style::default().add_modifier(Modifier::BOLD),
+            )]),
+        ]);
 
         // Add wrapped path lines
         for line in wrap_text(&path_str, content_width) {
@@ -186,9 +595,10 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
         }
 
         lines.push(Line::from(""));
-        lines.push(Line::from(vec![
-            Span::styled("Working Dir:", Style::default().add_modifier(Modifier::BOLD)),
-        ]));
+        lines.push(Line::from(vec![Span::styled(
+            "Working Dir:",
+            Style::default().add_modifier(Modifier::BOLD),
+        )]));
 
         // Add wrapped cwd lines
         for line in wrap_text(&cwd_str, content_width) {
@@ -196,16 +606,86 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
         }
 
         lines.push(Line::from(""));
-        lines.push(Line::from(vec![
-            Span::styled("Command:", Style::default().add_modifier(Modifier::BOLD)),
-        ]));
+        lines.push(Line::from(vec![Span::styled(
+            "Command:",
+            Style::default().add_modifier(Modifier::BOLD),
+        )]));
 
         // Add wrapped command lines
         for line in wrap_text(&cmd_str, content_width) {
             lines.push(Line::from(line));
         }
 
-        (format!(" Process Details (Port {}) ", p.port), lines)
+        if let Some(tree) = &app.tree {
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                label("Process Tree:"),
+                Span::raw(" \u{2191}/\u{2193} to move"),
+            ]));
+            for (i, (depth, entry)) in tree.entries().into_iter().enumerate() {
+                let text: String = format!(
+                    "{}{} {}  {}",
+                    "  ".repeat(depth.min(MAX_TREE_INDENT)),
+                    entry.pid,
+                    entry.name,
+                    entry.cmd
+                )
+                .chars()
+                .take(content_width)
+                .collect();
+                let style = if i == app.tree_cursor {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(Span::styled(text, style)));
+            }
+            if tree.more_children > 0 {
+                let indent = "  ".repeat((tree.ancestors.len() + 1).min(MAX_TREE_INDENT));
+                lines.push(Line::from(format!(
+                    "{indent}... {} more",
+                    tree.more_children
+                )));
+            }
+            if let Some(entry) = app.tree_selection() {
+                lines.push(Line::from(vec![Span::styled(
+                    format!("PID {}:", entry.pid),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )]));
+                for line in wrap_text(&entry.cmd, content_width) {
+                    lines.push(Line::from(line));
+                }
+            }
+        }
+
+        if app.is_selected_restricted() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::styled("Note:    ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw("path and working dir are not readable; run as root to see them"),
+            ]));
+        }
+
+        if let Some(entry) = app.get_selected_diff() {
+            let status = match entry.kind {
+                DiffKind::Added => "Added",
+                DiffKind::Removed => "Removed",
+                DiffKind::Changed => "Changed",
+                DiffKind::Unchanged => "Unchanged",
+            };
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::styled("Diff:    ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(status),
+            ]));
+            for change in &entry.changes {
+                for line in wrap_text(change, content_width) {
+                    lines.push(Line::from(line));
+                }
+            }
+        }
+
+        (title, lines)
     } else {
         (
             " Process Details ".to_string(),
@@ -213,6 +693,25 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
         )
     };
 
+    // Split popup into content and buttons, with a second row of buttons
+    // for a container, else a systemd unit or a stale binary
+    // A project row's actions cover all of its processes instead
+    let project = app.get_selected_project_group().is_some();
+    let container = !app.is_read_only() && !project && app.get_selected_container().is_some();
+    let unit = !app.is_read_only() && !project && !container && app.get_selected_unit().is_some();
+    let stale = !app.is_read_only() && !project && !container && !unit && app.is_selected_stale();
+    let button_rows: u16 = if container || unit || stale { 2 } else { 1 };
+
+    // Taller than the default only when a process tree needs the room
+    let needed = (details.len() as u16).saturating_add(3 + button_rows);
+    let popup_height = needed.max(24).min(area.height.saturating_sub(4));
+
+    // Center the popup
+    let popup_area = centered_rect(popup_width, popup_height, area);
+
+    // Clear the background
+    frame.render_widget(Clear, popup_area);
+
     // Popup styling
     let popup_bg = Color::Rgb(30, 35, 45);
     let border_color = Color::Rgb(100, 150, 200);
@@ -226,13 +725,13 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
         .border_style(Style::default().fg(border_color))
         .style(Style::default().bg(popup_bg));
 
-    // Split popup into content and buttons
     let inner = block.inner(popup_area);
     let chunks = Layout::vertical([
         Constraint::Min(0),
         Constraint::Length(1),
-        Constraint::Length(1),
-    ]).split(inner);
+        Constraint::Length(button_rows),
+    ])
+    .split(inner);
 
     frame.render_widget(block, popup_area);
 
@@ -243,19 +742,27 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
     let value_style = Style::default().fg(Color::Rgb(220, 220, 220));
 
     // Re-style the details with better colors
-    let styled_details: Vec<Line> = details.into_iter().map(|line| {
-        let spans: Vec<Span> = line.spans.into_iter().map(|span| {
-            if span.style.add_modifier.contains(Modifier::BOLD) {
-                Span::styled(span.content, label_style)
-            } else {
-                Span::styled(span.content, value_style)
-            }
-        }).collect();
-        Line::from(spans)
-    }).collect();
+    let styled_details: Vec<Line> = details
+        .into_iter()
+        .map(|line| {
+            let spans: Vec<Span> = line
+                .spans
+                .into_iter()
+                .map(|span| {
+                    if span.style.add_modifier.contains(Modifier::BOLD) {
+                        Span::styled(span.content, label_style)
+                    } else if span.style.add_modifier.contains(Modifier::REVERSED) {
+                        Span::styled(span.content, value_style.add_modifier(Modifier::REVERSED))
+                    } else {
+                        Span::styled(span.content, value_style)
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
 
-    let details_paragraph = Paragraph::new(styled_details)
-        .style(Style::default().bg(popup_bg));
+    let details_paragraph = Paragraph::new(styled_details).style(Style::default().bg(popup_bg));
     frame.render_widget(details_paragraph, chunks[0]);
 
     // Render buttons with better styling
@@ -289,21 +796,168 @@ fn render_terminate_popup(frame: &mut Frame, app: &App) {
     } else {
         button_danger
     };
+    let stop_style = if app.popup_selection == PopupButton::StopContainer {
+        button_danger_selected
+    } else {
+        button_danger
+    };
+    let restart_style = if app.popup_selection == PopupButton::RestartContainer {
+        button_selected
+    } else {
+        button_normal
+    };
+    let stop_unit_style = if app.popup_selection == PopupButton::StopUnit {
+        button_danger_selected
+    } else {
+        button_danger
+    };
+    let restart_unit_style = if app.popup_selection == PopupButton::RestartUnit {
+        button_selected
+    } else {
+        button_normal
+    };
+    let status_style = if app.popup_selection == PopupButton::UnitStatus {
+        button_selected
+    } else {
+        button_normal
+    };
+    let restart_process_style = if app.popup_selection == PopupButton::RestartProcess {
+        button_selected
+    } else {
+        button_normal
+    };
 
     let button_bg = Style::default().bg(popup_bg);
-    let buttons = Line::from(vec![
+    if app.is_read_only() {
+        let buttons = Line::from(vec![
+            Span::styled("  ", button_bg),
+            Span::styled(" Close (q) ", button_selected),
+            Span::styled("  read-only view", button_bg.fg(Color::Rgb(130, 130, 130))),
+        ]);
+        frame.render_widget(Paragraph::new(buttons).style(button_bg), chunks[2]);
+        return;
+    }
+
+    let (terminate, kill) = if project {
+        (" Terminate All (t) ", " Force Kill All (k) ")
+    } else {
+        (" Terminate (t) ", " Force Kill (k) ")
+    };
+    let mut buttons = vec![Line::from(vec![
         Span::styled("  ", button_bg),
         Span::styled(" Cancel (q) ", cancel_style),
         Span::styled("   ", button_bg),
-        Span::styled(" Terminate (t) ", term_style),
+        Span::styled(terminate, term_style),
         Span::styled("   ", button_bg),
-        Span::styled(" Force Kill (k) ", kill_style),
+        Span::styled(kill, kill_style),
         Span::styled("  ", button_bg),
-    ]);
+    ])];
+    if container {
+        buttons.push(Line::from(vec![
+            Span::styled("  Container: ", button_bg.fg(Color::Rgb(130, 130, 130))),
+            Span::styled(" Stop (s) ", stop_style),
+            Span::styled("   ", button_bg),
+            Span::styled(" Restart (r) ", restart_style),
+        ]));
+    } else if unit {
+        buttons.push(Line::from(vec![
+            Span::styled("  Unit: ", button_bg.fg(Color::Rgb(130, 130, 130))),
+            Span::styled(" Stop (s) ", stop_unit_style),
+            Span::styled("   ", button_bg),
+            Span::styled(" Restart (r) ", restart_unit_style),
+            Span::styled("   ", button_bg),
+            Span::styled(" Status (i) ", status_style),
+        ]));
+    } else if stale {
+        buttons.push(Line::from(vec![
+            Span::styled("  Stale binary: ", button_bg.fg(Color::Rgb(130, 130, 130))),
+            Span::styled(" Restart (r) ", restart_process_style),
+        ]));
+    }
 
     frame.render_widget(Paragraph::new(buttons).style(button_bg), chunks[2]);
 }
 
+/// `name (uid)` of a process owner, or whichever part is known
+fn render_elevation_prompt(frame: &mut Frame, elevation: &Elevation) {
+    let popup_area = centered_rect(60.min(frame.area().width), 6, frame.area());
+    frame.render_widget(Clear, popup_area);
+
+    let reason = match elevation {
+        Elevation::Scan => "Some sockets or processes are hidden from this user.",
+        Elevation::Kill { pids, .. } if pids.len() == 1 => {
+            "Permission denied: the process belongs to another user."
+        }
+        Elevation::Kill { .. } => "Permission denied: the processes belong to another user.",
+    };
+    let tool = elevate::tool().unwrap_or_else(|| "sudo".to_string());
+    let key_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let lines = vec![
+        Line::from(reason),
+        Line::from(""),
+        Line::from(format!("Run {tool} to {}?", elevation.describe())),
+        Line::from(vec![
+            Span::styled("y", key_style),
+            Span::raw(" yes  "),
+            Span::styled("n", key_style),
+            Span::raw(" no"),
+        ]),
+    ];
+
+    let block = Block::default()
+        .title(" Elevate ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+/// Confirm terminating the orphaned dev servers listed in `servers`
+fn render_cleanup_prompt(frame: &mut Frame, servers: &[(u32, String)]) {
+    // Long lists are cut short to keep the prompt on screen
+    const MAX_LISTED: usize = 8;
+
+    let key_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::from(format!(
+            "Terminate {} dev server{} left running without a terminal?",
+            servers.len(),
+            if servers.len() == 1 { "" } else { "s" }
+        )),
+        Line::from(""),
+    ];
+    for (pid, description) in servers.iter().take(MAX_LISTED) {
+        lines.push(Line::from(format!("  {pid:<8} {description}")));
+    }
+    if servers.len() > MAX_LISTED {
+        lines.push(Line::from(format!(
+            "  ... {} more",
+            servers.len() - MAX_LISTED
+        )));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", key_style),
+            Span::raw(" yes  "),
+            Span::styled("n", key_style),
+            Span::raw(" no"),
+        ]),
+    ]);
+
+    let height = (lines.len() as u16).saturating_add(2);
+    let popup_area = centered_rect(64.min(frame.area().width), height, frame.area());
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .title(" Clean Up Orphans ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
 fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
     let horizontal = Layout::horizontal([Constraint::Length(width)])
         .flex(Flex::Center)
@@ -363,3 +1017,284 @@ fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
 
     lines
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use ratatui::{backend::TestBackend, Terminal};
+    use std::path::PathBuf;
+
+    /// Set to regenerate the files in `tests/golden` from the current rendering
+    const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+    fn fixture_app() -> App {
+        let mut app = App::with_backend(Box::new(FakeBackend::from_fixture()));
+        app.wait_for_scan();
+        app
+    }
+
+    /// Render `app` into a `width` x `height` buffer, one line of text per row
+    fn render_text(app: &mut App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| render(frame, app)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let mut text = String::new();
+        for row in buffer.content.chunks(usize::from(width)) {
+            let line: String = row.iter().map(|cell| cell.symbol()).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Compare against `tests/golden/<name>.txt`, or rewrite it when
+    /// `UPDATE_GOLDEN` is set
+    fn assert_golden(name: &str, actual: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{name}.txt"));
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {e}; run with {UPDATE_ENV}=1 to create it",
+                path.display()
+            )
+        });
+        assert!(
+            expected == actual,
+            "{name} does not match {}; run with {UPDATE_ENV}=1 to update it\n\
+             --- expected\n{expected}--- actual\n{actual}",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn test_table_sizes() {
+        let mut app = fixture_app();
+        for (width, height) in [(120, 12), (80, 24), (40, 8), (10, 3)] {
+            assert_golden(
+                &format!("table_{width}x{height}"),
+                &render_text(&mut app, width, height),
+            );
+        }
+    }
+
+    #[test]
+    fn test_popup_sizes() {
+        let mut app = fixture_app();
+        app.next();
+        app.open_terminate_popup();
+        for (width, height) in [(100, 30), (60, 16), (20, 6), (6, 4)] {
+            assert_golden(
+                &format!("popup_{width}x{height}"),
+                &render_text(&mut app, width, height),
+            );
+        }
+    }
+
+    #[test]
+    fn test_unix_view() {
+        let mut app = fixture_app();
+        app.toggle_view();
+        app.wait_for_scan();
+        assert_golden("unix_120x10", &render_text(&mut app, 120, 10));
+        app.next();
+        app.open_terminate_popup();
+        assert_golden("unix_popup_100x30", &render_text(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn test_container_popup() {
+        let backend = FakeBackend::from_fixture();
+        let mut report = crate::backend::fixture_report();
+        report.ports[3].process_name = "docker-proxy".to_string();
+        report.ports[3].cmd_args = vec!["/usr/bin/docker-proxy".to_string()];
+        backend.set_report(report);
+        backend.set_containers(vec![crate::backend::fixture_container()]);
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        for _ in 0..3 {
+            app.next();
+        }
+        app.open_terminate_popup();
+        app.popup_prev();
+        assert_golden("container_popup_100x30", &render_text(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn test_unit_column() {
+        let backend = FakeBackend::from_fixture();
+        backend.set_unit(
+            1,
+            crate::systemd::Unit {
+                name: "sshd.socket".to_string(),
+                scope: crate::systemd::Scope::System,
+                activates: vec!["sshd.service".to_string()],
+            },
+        );
+        let mut report = crate::backend::fixture_report();
+        report.ports[0].pid = 1;
+        report.ports[0].process_name = "systemd".to_string();
+        backend.set_report(report);
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        assert_golden("unit_120x12", &render_text(&mut app, 120, 12));
+        app.open_terminate_popup();
+        assert_golden("unit_popup_100x30", &render_text(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn test_project_grouping() {
+        let backend = FakeBackend::from_fixture();
+        let shop = crate::project::Project {
+            name: "shop".to_string(),
+            root: "/home/dev/projects".into(),
+            branch: Some("main".to_string()),
+        };
+        backend.set_project(4242, shop.clone());
+        backend.set_project(5120, shop);
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        app.cycle_grouping();
+        app.cycle_grouping();
+        app.toggle_group();
+        assert_golden("project_120x12", &render_text(&mut app, 120, 12));
+        app.open_terminate_popup();
+        assert_golden("project_popup_100x30", &render_text(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn test_cleanup_prompt() {
+        let backend = FakeBackend::from_fixture();
+        backend.set_project(
+            4242,
+            crate::project::Project {
+                name: "web".to_string(),
+                root: "/home/dev/projects/web".into(),
+                branch: Some("main".to_string()),
+            },
+        );
+        backend.set_detached(4242);
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        app.offer_cleanup();
+        assert_golden("cleanup_120x16", &render_text(&mut app, 120, 16));
+    }
+
+    #[test]
+    fn test_stale_popup() {
+        let backend = FakeBackend::from_fixture();
+        backend.set_stale(5120);
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        for _ in 0..3 {
+            app.next();
+        }
+        app.open_terminate_popup();
+        assert_golden("stale_popup_100x30", &render_text(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn test_process_tree_popup() {
+        let backend = FakeBackend::from_fixture();
+        let entry = |pid: u32, name: &str, cmd: &str| crate::proctree::ProcEntry {
+            pid,
+            name: name.to_string(),
+            cmd: cmd.to_string(),
+        };
+        backend.set_tree(crate::proctree::ProcessTree {
+            ancestors: vec![
+                entry(1, "systemd", "/sbin/init"),
+                entry(900, "tmux: server", "tmux new -s dev"),
+                entry(4100, "bash", "-bash"),
+                entry(4200, "npm run dev", "npm run dev"),
+            ],
+            process: entry(
+                4242,
+                "node",
+                "node /home/dev/projects/web/node_modules/.bin/next dev",
+            ),
+            children: vec![entry(4300, "node", "node worker.js")],
+            more_children: 3,
+        });
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        app.next();
+        app.open_terminate_popup();
+        app.tree_prev();
+        assert_golden("tree_popup_100x40", &render_text(&mut app, 100, 40));
+    }
+
+    /// `count` listeners with distinct ports and paths
+    fn synthetic_ports(count: usize) -> Vec<port_checker::ports::PortInfo> {
+        let template = crate::backend::fixture_ports().remove(1);
+        (0..count)
+            .map(|i| {
+                let mut p = template.clone();
+                p.port = (i % 65_535) as u16 + 1;
+                p.pid = 10_000 + i as u32;
+                p.exe_path = Some(PathBuf::from(format!("/srv/app-{i}/bin/node")));
+                p
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_large_table_follows_selection() {
+        let mut app = App::with_backend(Box::new(FakeBackend::new(synthetic_ports(50_000))));
+        app.wait_for_scan();
+        app.table_state.select(Some(49_999));
+        let text = render_text(&mut app, 80, 10);
+        assert!(text.contains(">> 50000"), "{text}");
+        assert_eq!(app.table_state.offset(), 50_000 - 5);
+
+        // Moving up inside the window does not scroll it
+        app.previous();
+        let text = render_text(&mut app, 80, 10);
+        assert!(text.contains(">> 49999"), "{text}");
+        assert_eq!(app.table_state.offset(), 50_000 - 5);
+    }
+
+    #[test]
+    fn test_timeline_close_before_open() {
+        use crate::history::{Event, EventKind, History};
+
+        let ports = crate::backend::fixture_ports();
+        let event = |at, kind, i: usize| Event {
+            at,
+            kind,
+            port: ports[i].clone(),
+        };
+        // Recorded while the clock was set back
+        let history = History {
+            events: vec![
+                event(200, EventKind::Open, 0),
+                event(150, EventKind::Close, 0),
+                event(300, EventKind::Open, 1),
+            ],
+        };
+        let mut app = App::from_history(history);
+        let text = render_text(&mut app, 80, 8);
+        assert!(text.contains(&ports[0].port.to_string()), "{text}");
+    }
+
+    #[test]
+    fn test_scan_error() {
+        let backend = FakeBackend::from_fixture();
+        backend.fail_scans(Some("permission denied"));
+        let mut app = App::with_backend(Box::new(backend));
+        app.wait_for_scan();
+        for (width, height) in [(80, 8), (30, 3)] {
+            assert_golden(
+                &format!("error_{width}x{height}"),
+                &render_text(&mut app, width, height),
+            );
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Spawns child processes that own real sockets and checks what the scanner
+//! reports about them, and that the kill paths free their ports.
+//!
+//! The children are this test binary re-run with `--exact listener_child`
+//! and `CHILD_ENV` set, so the expected name, argv and exe are known.
+
+use port_checker::ports::{self, Collector, PortInfo, ScanOptions, Scanner};
+use port_checker::signal;
+use std::env;
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener, UdpSocket};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Set in children to the mode they run in: `listen` or `ignore-term`
+const CHILD_ENV: &str = "PORT_CHECKER_TEST_CHILD";
+
+/// Marks the child's reports on stdout, which libtest also writes to
+const REPORT_MARKER: &str = "@child ";
+
+/// How long to wait for a scan to reflect a child starting or exiting
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Child process body: bind sockets, report their ports on stdout, then
+/// wait to be killed. A no-op when run as a normal test.
+#[test]
+fn listener_child() {
+    let Ok(mode) = env::var(CHILD_ENV) else {
+        return;
+    };
+    if mode == "ignore-term" {
+        // SAFETY: nothing else in this process handles signals
+        unsafe {
+            nix::sys::signal::signal(
+                nix::sys::signal::Signal::SIGTERM,
+                nix::sys::signal::SigHandler::SigIgn,
+            )
+        }
+        .expect("ignore SIGTERM");
+    }
+
+    let tcp4 = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind tcp4");
+    let udp4 = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind udp4");
+    // IPv6 may be disabled in containers; report 0 so the parent skips it
+    let tcp6 = TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).ok();
+
+    println!("{REPORT_MARKER}tcp4 {}", tcp4.local_addr().unwrap().port());
+    println!("{REPORT_MARKER}udp4 {}", udp4.local_addr().unwrap().port());
+    println!(
+        "{REPORT_MARKER}tcp6 {}",
+        tcp6.as_ref().map_or(0, |l| l.local_addr().unwrap().port())
+    );
+    // Named after the PID, as parallel tests share the working directory
+    let unix_path = env::current_dir()
+        .unwrap()
+        .join(format!("child-{}.sock", std::process::id()));
+    let _unix = std::os::unix::net::UnixListener::bind(&unix_path).expect("bind unix");
+    println!("{REPORT_MARKER}unix {}", unix_path.display());
+    println!("{REPORT_MARKER}ready");
+
+    loop {
+        thread::sleep(Duration::from_secs(60));
+    }
+}
+
+/// A running child and the ports it bound
+struct Listener {
+    child: Child,
+    cwd: PathBuf,
+    tcp4: u16,
+    udp4: u16,
+    tcp6: Option<u16>,
+    /// Absolute path of the listening Unix socket
+    unix: PathBuf,
+}
+
+impl Listener {
+    fn spawn(mode: &str) -> Self {
+        Self::spawn_under(mode, &[])
+    }
+
+    /// Spawn the child through `wrapper`, a command line that runs the
+    /// program and arguments appended to it
+    fn spawn_under(mode: &str, wrapper: &[&str]) -> Self {
+        let cwd = env::temp_dir().join(format!("port-checker-{mode}-{}", std::process::id()));
+        std::fs::create_dir_all(&cwd).unwrap();
+        let cwd = cwd.canonicalize().unwrap();
+
+        let exe = env::current_exe().unwrap();
+        let mut command = match wrapper.split_first() {
+            Some((program, args)) => {
+                let mut command = Command::new(program);
+                command.args(args).arg(&exe);
+                command
+            }
+            None => Command::new(&exe),
+        };
+        let mut child = command
+            .args([
+                "--exact",
+                "listener_child",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(CHILD_ENV, mode)
+            .current_dir(&cwd)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("spawn child");
+
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        // Own the child before anything can panic, so Drop always reaps it
+        let mut listener = Self {
+            child,
+            cwd,
+            tcp4: 0,
+            udp4: 0,
+            tcp6: None,
+            unix: PathBuf::new(),
+        };
+        for line in stdout.lines() {
+            let line = line.unwrap();
+            // libtest's "test listener_child ... " may precede the first report
+            let Some((_, report)) = line.split_once(REPORT_MARKER) else {
+                continue;
+            };
+            match report.split_once(' ') {
+                Some(("tcp4", port)) => listener.tcp4 = port.parse().unwrap(),
+                Some(("udp4", port)) => listener.udp4 = port.parse().unwrap(),
+                Some(("tcp6", port)) => {
+                    listener.tcp6 = Some(port.parse().unwrap()).filter(|&p| p != 0)
+                }
+                Some(("unix", path)) => listener.unix = PathBuf::from(path),
+                _ if report == "ready" => break,
+                _ => panic!("unexpected child output: {line}"),
+            }
+        }
+        assert!(listener.tcp4 != 0, "child exited before binding");
+        listener
+    }
+
+    fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The listeners the scanner attributes to this child
+    fn scanned(&self) -> Vec<PortInfo> {
+        let report = ports::scan(&ScanOptions::default()).expect("scan");
+        report
+            .ports
+            .into_iter()
+            .filter(|p| p.pid == self.pid())
+            .collect()
+    }
+
+    /// Scan until `done` holds for this child's listeners
+    fn wait_for(&self, done: impl Fn(&[PortInfo]) -> bool) -> Vec<PortInfo> {
+        let start = Instant::now();
+        loop {
+            let found = self.scanned();
+            if done(&found) || start.elapsed() > SETTLE_TIMEOUT {
+                return found;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Whether the process has exited, waiting up to `SETTLE_TIMEOUT`
+    fn exited(&mut self) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < SETTLE_TIMEOUT {
+            if self.child.try_wait().unwrap().is_some() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.unix);
+        let _ = std::fs::remove_dir(&self.cwd);
+    }
+}
+
+#[test]
+fn test_scan_reports_child_listeners() {
+    let listener = Listener::spawn("listen");
+    let expected = 1 + usize::from(listener.tcp6.is_some());
+    let found = listener.wait_for(|found| found.len() >= expected);
+
+    let tcp4 = found
+        .iter()
+        .find(|p| p.port == listener.tcp4)
+        .expect("IPv4 listener reported");
+    assert_eq!(tcp4.address, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert_eq!(tcp4.cwd.as_ref(), Some(&listener.cwd));
+    assert_eq!(tcp4.exe_path, Some(env::current_exe().unwrap()));
+    assert!(tcp4.cmd_args.iter().any(|a| a == "listener_child"));
+    // The kernel truncates process names to 15 bytes
+    let exe_name = env::current_exe().unwrap();
+    let exe_name = exe_name.file_name().unwrap().to_string_lossy();
+    assert!(
+        !tcp4.process_name.is_empty() && exe_name.starts_with(&tcp4.process_name),
+        "{} is not a prefix of {exe_name}",
+        tcp4.process_name
+    );
+
+    if let Some(port) = listener.tcp6 {
+        let tcp6 = found
+            .iter()
+            .find(|p| p.port == port)
+            .expect("IPv6 listener reported");
+        assert_eq!(tcp6.address, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(tcp6.cwd.as_ref(), Some(&listener.cwd));
+    }
+
+    // Only TCP listeners are reported; a bound UDP socket is not listening
+    assert!(!found
+        .iter()
+        .any(|p| p.port == listener.udp4 && p.port != listener.tcp4));
+    assert_eq!(found.len(), expected);
+}
+
+#[test]
+fn test_terminate_frees_ports() {
+    let mut listener = Listener::spawn("listen");
+    assert!(!listener.wait_for(|found| !found.is_empty()).is_empty());
+
+    signal::terminate(listener.pid()).expect("SIGTERM");
+    assert!(listener.exited(), "child survived SIGTERM");
+    assert!(listener.scanned().is_empty());
+}
+
+#[test]
+fn test_force_kill_after_ignored_terminate() {
+    let mut listener = Listener::spawn("ignore-term");
+    assert!(!listener.wait_for(|found| !found.is_empty()).is_empty());
+
+    signal::terminate(listener.pid()).expect("SIGTERM");
+    thread::sleep(Duration::from_millis(300));
+    assert!(listener.child.try_wait().unwrap().is_none());
+    assert!(
+        !listener.scanned().is_empty(),
+        "ports released without exiting"
+    );
+
+    signal::force_kill(listener.pid()).expect("SIGKILL");
+    assert!(listener.exited(), "child survived SIGKILL");
+    assert!(listener.scanned().is_empty());
+}
+
+#[test]
+fn test_scanner_reuse_tracks_process_changes() {
+    let mut scanner = Scanner::new();
+    let owned_by = |scanner: &mut Scanner, pid: u32| -> Vec<PortInfo> {
+        let report = scanner.scan(&ScanOptions::default()).expect("scan");
+        report.ports.into_iter().filter(|p| p.pid == pid).collect()
+    };
+
+    let mut first = Listener::spawn("listen");
+    first.wait_for(|found| !found.is_empty());
+    let found = owned_by(&mut scanner, first.pid());
+    assert_eq!(found[0].cwd.as_ref(), Some(&first.cwd));
+
+    signal::force_kill(first.pid()).expect("SIGKILL");
+    assert!(first.exited());
+    assert!(owned_by(&mut scanner, first.pid()).is_empty());
+
+    // Cached details of the first child must not leak into the second
+    let second = Listener::spawn("ignore-term");
+    second.wait_for(|found| !found.is_empty());
+    let found = owned_by(&mut scanner, second.pid());
+    assert_eq!(found.len(), 1 + usize::from(second.tcp6.is_some()));
+    assert_eq!(found[0].cwd.as_ref(), Some(&second.cwd));
+    assert_eq!(found[0].exe_path, Some(env::current_exe().unwrap()));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sock_diag_matches_netstat2() {
+    let listener = Listener::spawn("listen");
+    let expected = 1 + usize::from(listener.tcp6.is_some());
+    let netstat2 = listener.wait_for(|found| found.len() >= expected);
+
+    let mut options = ScanOptions::default();
+    options.collector = Collector::SockDiag;
+    let mut scanner = Scanner::new();
+    // The second scan checks the cached socket owners
+    for _ in 0..2 {
+        let found: Vec<PortInfo> = scanner
+            .scan(&options)
+            .expect("sock-diag scan")
+            .ports
+            .into_iter()
+            .filter(|p| p.pid == listener.pid())
+            .collect();
+        assert_eq!(found.len(), netstat2.len());
+        for (diag, net) in found.iter().zip(&netstat2) {
+            assert_eq!((diag.port, diag.address), (net.port, net.address));
+            assert_eq!(diag.cwd, net.cwd);
+            let socket = diag.socket.as_ref().expect("socket details");
+            assert_eq!(socket.uid, nix::unistd::getuid().as_raw());
+            assert!(socket.inode != 0);
+            assert_eq!(socket.accept_queue, 0);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_scan_reports_unix_listener() {
+    let listener = Listener::spawn("listen");
+    let mut options = ScanOptions::default();
+    options.unix = true;
+
+    let report = ports::scan(&options).expect("scan");
+    let unix: Vec<_> = report
+        .unix_sockets
+        .iter()
+        .filter(|u| u.pid == listener.pid())
+        .collect();
+    assert_eq!(unix.len(), 1, "{unix:?}");
+    assert_eq!(unix[0].path, listener.unix.to_string_lossy());
+    assert_eq!(unix[0].cwd.as_ref(), Some(&listener.cwd));
+    assert!(unix[0].cmd_args.iter().any(|a| a == "listener_child"));
+    assert_eq!(unix[0].uid, Some(nix::unistd::getuid().as_raw()));
+
+    // Unix sockets are only listed when asked for
+    let report = ports::scan(&ScanOptions::default()).expect("scan");
+    assert!(report.unix_sockets.is_empty());
+}
+
+/// Whether this box lets us create network namespaces with unshare(1)
+#[cfg(target_os = "linux")]
+fn can_unshare() -> bool {
+    Command::new("unshare")
+        .args(["--net", "--map-root-user", "true"])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_scan_other_namespace() {
+    if !can_unshare() {
+        eprintln!("skipping: cannot create a network namespace with unshare");
+        return;
+    }
+    let listener = Listener::spawn_under("netns", &["unshare", "--net", "--map-root-user"]);
+
+    // The child's loopback is its own, so only a namespace scan finds it
+    assert!(listener.scanned().is_empty());
+
+    let mut options = ScanOptions::default();
+    options.namespaces = true;
+    let report = ports::scan(&options).expect("scan");
+    let found: Vec<PortInfo> = report
+        .ports
+        .into_iter()
+        .filter(|p| p.pid == listener.pid())
+        .collect();
+    let tcp4 = found
+        .iter()
+        .find(|p| p.port == listener.tcp4)
+        .expect("listener in the child's namespace");
+    let namespace = tcp4.namespace.as_ref().expect("namespace recorded");
+    assert!(namespace.name.starts_with("net:["), "{}", namespace.name);
+    assert_eq!(tcp4.cwd.as_ref(), Some(&listener.cwd));
+}